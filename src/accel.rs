@@ -0,0 +1,338 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::escape::EscapeSample;
+use crate::fractal::{view_point, Fractal, Orbit};
+use crate::scheduler::{Scheduler, Tile};
+
+/// Ways of skipping the iterations for pixels that are surely inside the set.
+///
+/// Both rely on the set (and every iteration-limited approximation of it)
+/// having no holes: if the border of a region is inside the set, so is all of
+/// it. That holds for the Mandelbrot and Julia sets, but pixels only sample
+/// the border, and an escaping channel thinner than a pixel can slip between
+/// two interior ones. So a region is only filled when a border `MARGIN`
+/// pixels thick around it is all inside, which a channel would have to slip
+/// through that many times unseen. This is a heuristic, not an exact rule:
+/// on the views in the tests the result is the same as iterating every
+/// pixel, bit for bit, but a view with fine enough filaments can come out
+/// differently, with escaping pixels filled as interior. So the default is
+/// `None` and `--accel` opts in.
+/// Only the interior is filled because escaped pixels each carry their own
+/// smooth count, final `z` and distance estimate, which no fill could
+/// reproduce exactly.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Accel {
+    /// Iterate every pixel.
+    #[serde(alias = "off")]
+    None,
+    /// Compute the border of a rectangle; if it is all inside the set, fill
+    /// the rectangle, and otherwise split what's inside the border in two and
+    /// try again.
+    #[serde(alias = "mariani")]
+    MarianiSilver,
+    /// Compute the pixels outside the set, spreading inwards from the edges,
+    /// so only a band along the boundary of the interior is ever iterated.
+    #[serde(rename = "boundary")]
+    BoundaryTrace,
+}
+
+pub const ACCELS: &str = "none (default)\nmariani-silver\nboundary\n";
+
+impl FromStr for Accel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "none" | "off" => Ok(Accel::None),
+            "mariani-silver" | "mariani" => Ok(Accel::MarianiSilver),
+            "boundary" => Ok(Accel::BoundaryTrace),
+            _ => Err(format!("unknown acceleration {}", s)),
+        }
+    }
+}
+
+/// Rectangles smaller than this on either side are iterated in full rather
+/// than split again.
+const MIN_SPLIT: usize = 6;
+
+/// How thick the border of a region has to be, all of it inside the set,
+/// for the region to be filled. Boundary tracing gets this by iterating
+/// every pixel this close to an escaping one.
+const MARGIN: usize = 3;
+
+/// Lazily computed samples for one tile, so that no pixel is iterated twice.
+struct TileSamples<'a> {
+    fractal: Fractal,
+    limit: usize,
+    orbit: Orbit,
+    rotation: f64,
+    samples: &'a mut [EscapeSample],
+    done: Vec<bool>,
+    tile: &'a Tile,
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+}
+
+impl<'a> TileSamples<'a> {
+    /// The sample at column `col` and row `row` of the tile, iterating it if
+    /// it hasn't been already.
+    fn get(&mut self, col: usize, row: usize) -> EscapeSample {
+        let i = row * self.tile.width + col;
+        if !self.done[i] {
+            let pixel = ((self.tile.x + col) as f64, (self.tile.y + row) as f64);
+            let point = view_point(self.bounds, pixel, self.upper_left, self.lower_right, self.rotation);
+            self.samples[i] = self.fractal.escape(point, self.limit, self.orbit);
+            self.done[i] = true;
+        }
+        self.samples[i]
+    }
+
+    fn fill_interior(&mut self, left: usize, top: usize, right: usize, bottom: usize) {
+        for row in top..bottom {
+            for col in left..right {
+                let i = row * self.tile.width + col;
+                self.samples[i] = EscapeSample::INTERIOR;
+                self.done[i] = true;
+            }
+        }
+    }
+
+    /// Mariani-Silver subdivision of the half-open rectangle
+    /// `[left, right) x [top, bottom)`.
+    fn subdivide(&mut self, left: usize, top: usize, right: usize, bottom: usize) {
+        let (width, height) = (right - left, bottom - top);
+        if width < MIN_SPLIT.max(2 * MARGIN + 1) || height < MIN_SPLIT.max(2 * MARGIN + 1) {
+            for row in top..bottom {
+                for col in left..right {
+                    self.get(col, row);
+                }
+            }
+            return
+        }
+
+        let mut all_interior = true;
+        for row in top..bottom {
+            let edge = row < top + MARGIN || row >= bottom - MARGIN;
+            for col in left..right {
+                if edge || col < left + MARGIN || col >= right - MARGIN {
+                    all_interior &= self.get(col, row).interior;
+                }
+            }
+        }
+        let (left, top, right, bottom) = (left + MARGIN, top + MARGIN, right - MARGIN, bottom - MARGIN);
+        if all_interior {
+            self.fill_interior(left, top, right, bottom);
+            return
+        }
+
+        // the border is already known, so split what's inside it
+        let (width, height) = (right - left, bottom - top);
+        if width >= height {
+            let middle = left + width / 2;
+            self.subdivide(left, top, middle, bottom);
+            self.subdivide(middle, top, right, bottom);
+        } else {
+            let middle = top + height / 2;
+            self.subdivide(left, top, right, middle);
+            self.subdivide(left, middle, right, bottom);
+        }
+    }
+
+    /// Iterate the edges of the half-open rectangle
+    /// `[left, right) x [top, bottom)`, then every pixel in it within
+    /// `MARGIN` of one that escaped. Returns whether any pixel of the
+    /// rectangle was left unvisited, which is then walled in by the set.
+    fn trace(&mut self, left: usize, top: usize, right: usize, bottom: usize) -> bool {
+        let mut queue = VecDeque::new();
+        for col in left..right {
+            queue.push_back((col, top));
+            queue.push_back((col, bottom - 1));
+        }
+        for row in top + 1..bottom.saturating_sub(1) {
+            queue.push_back((left, row));
+            queue.push_back((right - 1, row));
+        }
+        let width = self.tile.width;
+        let mut queued = vec![false; self.tile.len()];
+        for &(col, row) in queue.iter() {
+            queued[row * width + col] = true;
+        }
+
+        let reach = MARGIN as isize;
+        while let Some((col, row)) = queue.pop_front() {
+            if self.get(col, row).interior {
+                continue
+            }
+            for (dc, dr) in (-reach..=reach).flat_map(|dr| (-reach..=reach).map(move |dc| (dc, dr))) {
+                let (c, r) = (col.wrapping_add_signed(dc), row.wrapping_add_signed(dr));
+                if (left..right).contains(&c) && (top..bottom).contains(&r) && !queued[r * width + c] {
+                    queued[r * width + c] = true;
+                    queue.push_back((c, r));
+                }
+            }
+        }
+        (top..bottom).any(|row| (left..right).any(|col| !self.done[row * width + col]))
+    }
+}
+
+/// Render `tile` of an image of size `bounds` into `samples` like
+/// `Scheduler::render_tile`, using the scheduler's acceleration to skip the
+/// set's interior when `fractal` and the power allow it. Returns false,
+/// leaving `samples` alone, if they don't.
+#[allow(clippy::too_many_arguments)]
+pub fn render_tile(scheduler: &Scheduler,
+                   fractal: Fractal,
+                   orbit: Orbit,
+                   rotation: f64,
+                   samples: &mut [EscapeSample],
+                   tile: &Tile,
+                   bounds: (usize, usize),
+                   upper_left: Complex<f64>,
+                   lower_right: Complex<f64>) -> bool {
+    if scheduler.accel == Accel::None || !fractal.is_full() || !orbit.is_full() || tile.is_empty() {
+        return false
+    }
+    match scheduler.accel {
+        Accel::MarianiSilver => {
+            let mut lazy = TileSamples {
+                fractal,
+                limit: scheduler.limit,
+                orbit,
+                rotation,
+                samples,
+                done: vec![false; tile.len()],
+                tile,
+                bounds,
+                upper_left,
+                lower_right,
+            };
+            lazy.subdivide(0, 0, tile.width, tile.height);
+        }
+        Accel::BoundaryTrace => trace_tile(scheduler, fractal, orbit, rotation, samples, tile, bounds, upper_left, lower_right),
+        Accel::None => unreachable!(),
+    }
+    true
+}
+
+/// Boundary tracing for `tile`. Escaping pixels just outside the tile have
+/// to be spread from too, so if tracing the tile on its own leaves anything
+/// to fill, it is traced again grown by `MARGIN` on every side, as far as the
+/// image goes.
+#[allow(clippy::too_many_arguments)]
+fn trace_tile(scheduler: &Scheduler,
+              fractal: Fractal,
+              orbit: Orbit,
+              rotation: f64,
+              samples: &mut [EscapeSample],
+              tile: &Tile,
+              bounds: (usize, usize),
+              upper_left: Complex<f64>,
+              lower_right: Complex<f64>) {
+    let (left, top) = (tile.x.saturating_sub(MARGIN), tile.y.saturating_sub(MARGIN));
+    let grown = Tile {
+        x: left,
+        y: top,
+        width: (tile.x + tile.width + MARGIN).min(bounds.0) - left,
+        height: (tile.y + tile.height + MARGIN).min(bounds.1) - top,
+    };
+    let mut buffer = vec![EscapeSample::INTERIOR; grown.len()];
+    let mut lazy = TileSamples {
+        fractal,
+        limit: scheduler.limit,
+        orbit,
+        rotation,
+        samples: &mut buffer,
+        done: vec![false; grown.len()],
+        tile: &grown,
+        bounds,
+        upper_left,
+        lower_right,
+    };
+    let (x, y) = (tile.x - left, tile.y - top);
+    if lazy.trace(x, y, x + tile.width, y + tile.height) {
+        lazy.trace(0, 0, grown.width, grown.height);
+    }
+    // what was never iterated is already the interior in `buffer`
+    for (row, line) in samples.chunks_mut(tile.width).enumerate() {
+        let start = (y + row) * grown.width + x;
+        line.copy_from_slice(&buffer[start..start + tile.width]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type View = (Fractal, (usize, usize), Complex<f64>, Complex<f64>);
+
+    /// Views that exercise big interiors, thin filaments and tile edges.
+    fn known_views() -> Vec<View> {
+        vec![
+            (Fractal::Mandelbrot, (160, 120), Complex { re: -2.0, im: 1.125 }, Complex { re: 1.0, im: -1.125 }),
+            (Fractal::Mandelbrot, (128, 128), Complex { re: -0.8, im: 0.2 }, Complex { re: -0.7, im: 0.1 }),
+            (Fractal::Mandelbrot, (100, 100), Complex { re: -1.8, im: 0.05 }, Complex { re: -1.7, im: -0.05 }),
+            (Fractal::Julia(Complex { re: -0.123, im: 0.745 }), (128, 96), Complex { re: -1.6, im: 1.2 }, Complex { re: 1.6, im: -1.2 }),
+            (Fractal::Julia(Complex { re: -0.8, im: 0.156 }), (120, 120), Complex { re: -1.5, im: 1.5 }, Complex { re: 1.5, im: -1.5 }),
+            (Fractal::Julia(Complex { re: -1.0, im: 0.0 }), (90, 70), Complex { re: -2.0, im: 1.2 }, Complex { re: 2.0, im: -1.2 }),
+            (Fractal::Mandelbrot, (200, 150), Complex { re: -0.76, im: 0.12 }, Complex { re: -0.72, im: 0.09 }),
+            (Fractal::Mandelbrot, (150, 150), Complex { re: 0.25, im: 0.02 }, Complex { re: 0.29, im: -0.02 }),
+            (Fractal::Mandelbrot, (160, 120), Complex { re: -0.17, im: 1.06 }, Complex { re: -0.13, im: 1.03 }),
+            (Fractal::Mandelbrot, (300, 200), Complex { re: -2.2, im: 1.2 }, Complex { re: 0.8, im: -0.8 }),
+            (Fractal::Julia(Complex { re: 0.285, im: 0.01 }), (160, 120), Complex { re: -1.6, im: 1.2 }, Complex { re: 1.6, im: -1.2 }),
+            (Fractal::Julia(Complex { re: -0.4, im: 0.6 }), (160, 160), Complex { re: -1.5, im: 1.5 }, Complex { re: 1.5, im: -1.5 }),
+        ]
+    }
+
+    #[test]
+    fn test_accelerated_matches_brute_force() {
+        for (fractal, bounds, upper_left, lower_right) in known_views() {
+            for tile_size in [16, 40, 1000] {
+                let tiles = Scheduler { tile_size, ..Default::default() }.tiles(bounds);
+                for tile in tiles.iter() {
+                    let mut expected = vec![EscapeSample::INTERIOR; tile.len()];
+                    Scheduler { accel: Accel::None, ..Default::default() }.render_tile(fractal, Orbit::default(), 0.0, &mut expected, tile, bounds, upper_left, lower_right);
+                    for accel in [Accel::MarianiSilver, Accel::BoundaryTrace] {
+                        let mut samples = vec![EscapeSample::default(); tile.len()];
+                        assert!(render_tile(&Scheduler { accel, ..Default::default() }, fractal, Orbit::default(), 0.0, &mut samples, tile, bounds, upper_left, lower_right));
+                        assert!(samples == expected, "{:?} differs on {:?} {:?}", accel, fractal, tile);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_burning_ship_is_not_accelerated() {
+        let tile = Tile { x: 0, y: 0, width: 8, height: 8 };
+        let mut samples = vec![EscapeSample::default(); tile.len()];
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 });
+        assert!(!render_tile(&Scheduler { accel: Accel::MarianiSilver, ..Default::default() }, Fractal::BurningShip, Orbit::default(), 0.0, &mut samples, &tile, (8, 8), upper_left, lower_right));
+    }
+
+    #[test]
+    fn test_turned_powers_match_brute_force() {
+        let bounds = (120, 90);
+        let (upper_left, lower_right) = (Complex { re: -1.6, im: 1.2 }, Complex { re: 1.6, im: -1.2 });
+        let scheduler = Scheduler { tile_size: 32, ..Default::default() };
+        let cube = Orbit { power: 3.0, bailout: 10.0 };
+        for tile in scheduler.tiles(bounds).iter() {
+            let mut expected = vec![EscapeSample::INTERIOR; tile.len()];
+            scheduler.render_tile(Fractal::Mandelbrot, cube, 30.0, &mut expected, tile, bounds, upper_left, lower_right);
+            for accel in [Accel::MarianiSilver, Accel::BoundaryTrace] {
+                let mut samples = vec![EscapeSample::default(); tile.len()];
+                assert!(render_tile(&Scheduler { accel, ..scheduler }, Fractal::Mandelbrot, cube, 30.0, &mut samples, tile, bounds, upper_left, lower_right));
+                assert!(samples == expected, "{:?} differs on {:?}", accel, tile);
+            }
+        }
+        let tile = Tile { x: 0, y: 0, width: 8, height: 8 };
+        let mut samples = vec![EscapeSample::default(); tile.len()];
+        let fractional = Orbit { power: 2.5, ..Orbit::default() };
+        assert!(!render_tile(&Scheduler { accel: Accel::MarianiSilver, ..Default::default() }, Fractal::Mandelbrot, fractional, 0.0, &mut samples, &tile, (8, 8), upper_left, lower_right));
+    }
+}
@@ -0,0 +1,1319 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::mpsc;
+
+use color_quant::NeuQuant;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{imageops, Delay, Frame, ImageError, RgbaImage};
+use num::Complex;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+use crate::error::{Error, Result};
+use crate::escape::EscapeField;
+use crate::fractal::{fit_viewport, parse_complex, Fractal, Orbit, BAILOUT, POWER};
+use crate::monocub::{interpolate, monotonic_cubic_preprocess};
+use crate::output::{to_image, Depth};
+use crate::progress;
+use crate::request::RenderRequest;
+use crate::scene::is_json;
+
+/// How many finished frames may wait to be written before rendering stops
+/// to let the writer catch up.
+const FRAMES_IN_FLIGHT: usize = 2;
+
+/// How many pixels of frames may be rendered at once, when the frames are
+/// small enough that a few of them keep more threads busy than one.
+const BATCH_PIXELS: usize = 1 << 21;
+
+/// How many points along a seed path are marked on an inset map.
+const INSET_TRACE_POINTS: usize = 1000;
+
+/// The NeuQuant sampling factor for GIF palettes, from 1 (best) to 30
+/// (fastest). 10 is what it suggests.
+const GIF_QUANTIZER_SPEED: i32 = 10;
+
+/// A zoom from the view of `start` down into `target`.
+///
+/// The width of the view shrinks by the same factor from each frame to the
+/// next, so the zoom seems to go at a steady speed, and the center drifts
+/// towards `target` just fast enough to arrive there on the last frame.
+#[derive(Clone, Debug)]
+pub struct Zoom {
+    /// The first frame. Everything but its region and iteration limit is
+    /// the same for every frame.
+    pub start: RenderRequest,
+    /// The center of the last frame.
+    pub target: Complex<f64>,
+    /// How many times narrower the last frame is than the first.
+    pub depth: f64,
+    pub frames: usize,
+    /// Iterations added to the limit for every tenfold zoom, since details
+    /// deeper in need more of them.
+    pub iteration_growth: f64,
+}
+
+/// An animation, which is a request to render for every moment of it.
+pub trait Animation: Sync {
+    /// How many frames there are.
+    fn frames(&self) -> usize;
+
+    /// The request rendering the moment `time`, counted in frames from the
+    /// first. Times between frames are for motion blur, and times a little
+    /// outside the frames should give a sensible picture too.
+    fn at(&self, time: f64) -> RenderRequest;
+
+    /// The request rendering frame `index`, counting from 0.
+    fn frame(&self, index: usize) -> RenderRequest {
+        self.at(index as f64)
+    }
+
+    /// The escape field for `request`, one of this animation's.
+    fn field(&self, request: &RenderRequest) -> Cow<'_, EscapeField> {
+        let mut field = request.field();
+        request.scheduler.render(&mut field, request.fractal, request.orbit, request.rotation, |_, _| ());
+        Cow::Owned(field)
+    }
+
+    /// Color `field`, made by `field` for `request`.
+    fn colorize(&self, request: &RenderRequest, field: &EscapeField) -> Vec<f32> {
+        request.colorize(field)
+    }
+
+    /// Render every frame in order, as `render_frames` does, without motion
+    /// blur.
+    fn render<F>(&self, on_frame: F) -> Result<()>
+        where F: FnMut(usize, &RenderRequest, &EscapeField, Vec<f32>) -> Result<()> {
+        render_frames(self, None, on_frame)
+    }
+}
+
+impl Animation for Zoom {
+    fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn at(&self, time: f64) -> RenderRequest {
+        let t = if self.frames > 1 { time / (self.frames - 1) as f64 } else { 0.0 };
+        let scale = self.depth.powf(-t);
+        // how far along the way to the target the center is, which is as far
+        // as the zoom is when measured in widths
+        let along = if self.depth == 1.0 { t } else { (1.0 - scale) / (1.0 - 1.0 / self.depth) };
+
+        let (upper_left, lower_right) = (self.start.upper_left, self.start.lower_right);
+        let start_center = (upper_left + lower_right) / 2.0;
+        let center = start_center + (self.target - start_center) * along;
+        let half = (lower_right - upper_left) * scale / 2.0;
+        let mut request = self.start.clone().viewport(center - half, center + half);
+        let growth = self.iteration_growth * t * self.depth.log10();
+        request.scheduler.limit = (self.start.scheduler.limit as f64 + growth).round().max(1.0) as usize;
+        request
+    }
+}
+
+/// Motion blur: every frame is the average of `samples` moments, spread
+/// evenly over the time the shutter is open around it. Each moment is
+/// supersampled on its own, if the animation's requests ask for it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MotionBlur {
+    pub samples: usize,
+    /// How long the shutter is open, in degrees of the 360 a frame lasts.
+    /// 180 is what film cameras mostly use.
+    pub shutter: f64,
+}
+
+impl MotionBlur {
+    /// The moments frame `index` is the average of, in the middle of equal
+    /// parts of the time the shutter is open, which is centered on the
+    /// frame.
+    pub fn times(&self, index: usize) -> Vec<f64> {
+        let open = self.shutter / 360.0;
+        (0..self.samples)
+            .map(|k| index as f64 + open * ((k as f64 + 0.5) / self.samples as f64 - 0.5))
+            .collect()
+    }
+}
+
+/// Render the frames of `animation` in order, handing each to `on_frame`
+/// with its request, its escape field and its colored pixels. Small frames
+/// are rendered several at once, and the next frames are rendered while
+/// `on_frame` deals with the last ones.
+///
+/// With `blur`, the moments of each frame are colored and then averaged in
+/// linear light, and `on_frame` gets the field of the middle one.
+///
+/// If the render is cancelled, the frames in progress are dropped and no
+/// more are started. The first error from `on_frame` stops the render and is
+/// returned.
+pub fn render_frames<A, F>(animation: &A, blur: Option<&MotionBlur>, mut on_frame: F) -> Result<()>
+    where A: Animation + ?Sized,
+          F: FnMut(usize, &RenderRequest, &EscapeField, Vec<f32>) -> Result<()> {
+    let frames = animation.frames();
+    if frames == 0 {
+        return Ok(())
+    }
+    animation.frame(0).validate()?;
+    let samples = blur.map_or(1, |blur| blur.samples.max(1));
+    let times = |index: usize| blur.map_or_else(|| vec![index as f64], |blur| blur.times(index));
+    let (sender, receiver) = mpsc::sync_channel(FRAMES_IN_FLIGHT);
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut index = 0;
+            while index < frames {
+                let (width, height) = animation.frame(index).bounds;
+                let batch = (BATCH_PIXELS / (width * height * samples).max(1))
+                    .clamp(1, rayon::current_num_threads())
+                    .min(frames - index);
+                let moments: Vec<(usize, f64)> = (index..index + batch)
+                    .flat_map(|index| times(index).into_iter().map(move |time| (index, time)))
+                    .collect();
+                let mut rendered: Vec<_> = moments.into_par_iter().map(|(index, time)| {
+                    let request = animation.at(time);
+                    let field = animation.field(&request);
+                    let pixels = animation.colorize(&request, &field);
+                    (index, field, pixels)
+                }).collect();
+                // a cancelled frame is missing tiles
+                if progress::cancelled() {
+                    return
+                }
+                while !rendered.is_empty() {
+                    let moments: Vec<_> = rendered.drain(..samples).collect();
+                    let request = animation.frame(moments[0].0);
+                    let frame = if samples == 1 {
+                        let (index, field, pixels) = moments.into_iter().next().unwrap();
+                        (index, request, field, pixels)
+                    } else {
+                        let pixels = blend(moments.iter().map(|(_, _, pixels)| &pixels[..]), request.coloring.channels());
+                        let (index, field, _) = moments.into_iter().nth(samples / 2).unwrap();
+                        (index, request, field, pixels)
+                    };
+                    if sender.send(frame).is_err() {
+                        return
+                    }
+                }
+                index += batch;
+            }
+        });
+        for (index, request, field, pixels) in receiver {
+            on_frame(index, &request, &field, pixels)?;
+        }
+        Ok(())
+    })
+}
+
+/// Average frames of colored pixels in linear light, weighted by alpha so
+/// that transparent moments don't darken the others, as supersampling does.
+fn blend<'a, I>(frames: I, channels: usize) -> Vec<f32> where I: ExactSizeIterator<Item = &'a [f32]> {
+    let count = frames.len() as f32;
+    let mut sums: Vec<f32> = Vec::new();
+    for pixels in frames {
+        if sums.is_empty() {
+            sums = vec![0.0; pixels.len()];
+        }
+        for (sum, pixel) in sums.chunks_mut(channels).zip(pixels.chunks(channels)) {
+            let alpha = if channels == 4 { pixel[3] } else { 1.0 };
+            for c in 0..3 {
+                sum[c] += alpha * srgb_to_linear(pixel[c]);
+            }
+            if channels == 4 {
+                sum[3] += alpha;
+            }
+        }
+    }
+    for pixel in sums.chunks_mut(channels) {
+        let coverage = if channels == 4 { pixel[3] } else { count };
+        for v in &mut pixel[..3] {
+            *v = if coverage != 0.0 { linear_to_srgb((*v / coverage).clamp(0.0, 1.0)) } else { 0.0 };
+        }
+        if channels == 4 {
+            pixel[3] = coverage / count;
+        }
+    }
+    sums
+}
+
+/// How a parameter gets from one keyframe to the next.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Interpolation {
+    /// At a steady rate, turning sharply at every key.
+    Linear,
+    /// Slowing to a stop at every key and setting off again.
+    Ease,
+    /// A Catmull-Rom spline, which runs smoothly through the keys but may
+    /// overshoot them.
+    CatmullRom,
+    /// The monotonic cubic spline the palettes use, which runs smoothly
+    /// through the keys without overshooting them.
+    #[default]
+    Monotonic,
+}
+
+pub const INTERPOLATIONS: &str = "monotonic (default)\ncatmull-rom\nease\nlinear\n";
+
+impl FromStr for Interpolation {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "linear" => Ok(Interpolation::Linear),
+            "ease" | "ease-in-out" => Ok(Interpolation::Ease),
+            "catmull-rom" => Ok(Interpolation::CatmullRom),
+            "monotonic" => Ok(Interpolation::Monotonic),
+            _ => Err(format!("unknown interpolation {}", s)),
+        }
+    }
+}
+
+/// The values some parameters take at one frame. A parameter left out
+/// follows the keyframes around this one that do set it, or keeps the
+/// value in the scene if none do.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// The frame this is the key of, counting from 0.
+    pub frame: usize,
+    /// The center of the view, as `[re, im]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center: Option<[f64; 2]>,
+    /// How many times narrower the view is than the scene's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f64>,
+    /// The seed of a Julia set, as `[re, im]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<[f64; 2]>,
+    /// How far around the palette the colors are moved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette_offset: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iterations: Option<usize>,
+    /// How far the view is turned counterclockwise about its center, in
+    /// degrees.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<f64>,
+    /// The power `z` is raised to at every step.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power: Option<f64>,
+    /// How far from 0 an orbit has to get to escape.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bailout: Option<f64>,
+}
+
+/// A keyframe file, which animates a scene:
+///
+/// ```toml
+/// interpolation = "catmull-rom"
+///
+/// [[keyframe]]
+/// frame = 0
+/// zoom = 1.0
+///
+/// [[keyframe]]
+/// frame = 120
+/// center = [-0.7436, 0.1318]
+/// zoom = 1e4
+/// iterations = 2000
+/// palette_offset = 0.5
+/// rotation = 90.0
+/// power = 3.0
+/// ```
+///
+/// The keyframes have to be in order of their frames. Zooms are
+/// interpolated by their logarithms, so a zoom between two keys goes at a
+/// steady speed. Powers and bailouts never go below the lowest key, even
+/// where a spline would overshoot it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframes {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    #[serde(rename = "keyframe")]
+    pub keys: Vec<Keyframe>,
+}
+
+impl Keyframes {
+    pub fn from_toml(s: &str) -> Result<Keyframes> {
+        toml::from_str(s).map_err(|e| Error::BadKeyframes(e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> Result<Keyframes> {
+        serde_json::from_str(s).map_err(|e| Error::BadKeyframes(e.to_string()))
+    }
+
+    /// Read the keyframe file at `path`, which is JSON if its name ends in
+    /// `.json` and TOML otherwise.
+    pub fn read(path: &str) -> Result<Keyframes> {
+        let text = fs::read_to_string(path).map_err(|e| Error::from(e).in_file(path))?;
+        if is_json(path) { Keyframes::from_json(&text) } else { Keyframes::from_toml(&text) }.map_err(|e| e.in_file(path))
+    }
+
+    /// The animation of `start` these keyframes describe, `frames` long, or
+    /// up to the last keyframe if that isn't given.
+    pub fn animate(&self, start: RenderRequest, frames: Option<usize>) -> Result<KeyframeAnimation> {
+        start.validate()?;
+        let bad = |reason: String| Err(Error::BadKeyframes(reason));
+        let last = match self.keys.last() {
+            Some(key) => key.frame,
+            None => return bad("there has to be at least one keyframe".to_string()),
+        };
+        if let Some(pair) = self.keys.windows(2).find(|pair| pair[0].frame >= pair[1].frame) {
+            return bad(format!("keyframes have to be in order, but frame {} is followed by frame {}", pair[0].frame, pair[1].frame));
+        }
+        for key in self.keys.iter() {
+            if key.zoom.is_some_and(|zoom| !(zoom.is_finite() && zoom > 0.0)) {
+                return bad(format!("the zoom at frame {} has to be above zero", key.frame));
+            }
+            if key.seed.is_some() && !matches!(start.fractal, Fractal::Julia(_)) {
+                return bad(format!("frame {} has a seed, but only a julia set has one", key.frame));
+            }
+            if key.iterations == Some(0) {
+                return bad(format!("frame {} has no iterations", key.frame));
+            }
+            if key.rotation.is_some_and(|rotation| !rotation.is_finite()) {
+                return bad(format!("the rotation at frame {} has to be a number of degrees", key.frame));
+            }
+            let orbit = Orbit { power: key.power.unwrap_or(POWER), bailout: key.bailout.unwrap_or(BAILOUT) };
+            if let Err(reason) = orbit.check() {
+                return bad(format!("at frame {}, {}", key.frame, reason));
+            }
+        }
+
+        let curve = |value: &dyn Fn(&Keyframe) -> Option<f64>| Curve::keyed(self.interpolation, &self.keys, value);
+        Ok(KeyframeAnimation {
+            frames: frames.unwrap_or(last + 1),
+            center: [curve(&|key| key.center.map(|c| c[0])), curve(&|key| key.center.map(|c| c[1]))],
+            zoom: curve(&|key| key.zoom.map(f64::ln)),
+            seed: [curve(&|key| key.seed.map(|s| s[0])), curve(&|key| key.seed.map(|s| s[1]))],
+            palette_offset: curve(&|key| key.palette_offset),
+            iterations: curve(&|key| key.iterations.map(|limit| limit as f64)),
+            rotation: curve(&|key| key.rotation),
+            power: curve(&|key| key.power),
+            bailout: curve(&|key| key.bailout),
+            start,
+        })
+    }
+}
+
+/// The values of one parameter at its keyframes, and the way between them.
+#[derive(Clone, Debug)]
+struct Curve {
+    interpolation: Interpolation,
+    frames: Vec<f64>,
+    values: Vec<f64>,
+    /// The slope at every key, for the splines.
+    slopes: Vec<f64>,
+}
+
+impl Curve {
+    /// The curve through the keys where `value` has something, if any do.
+    fn keyed(interpolation: Interpolation, keys: &[Keyframe], value: &dyn Fn(&Keyframe) -> Option<f64>) -> Option<Curve> {
+        let (frames, values): (Vec<f64>, Vec<f64>) = keys.iter()
+            .filter_map(|key| value(key).map(|v| (key.frame as f64, v)))
+            .unzip();
+        if frames.is_empty() { None } else { Some(Curve::new(interpolation, frames, values)) }
+    }
+
+    /// The curve through `values` at `frames`, which must be increasing.
+    fn new(interpolation: Interpolation, frames: Vec<f64>, values: Vec<f64>) -> Curve {
+        let n = frames.len();
+        let slopes = match interpolation {
+            _ if n < 2 => Vec::new(),
+            Interpolation::Monotonic => monotonic_cubic_preprocess(&values, &frames),
+            Interpolation::CatmullRom => (0..n).map(|i| {
+                let (before, after) = (i.saturating_sub(1), (i + 1).min(n - 1));
+                (values[after] - values[before]) / (frames[after] - frames[before])
+            }).collect(),
+            Interpolation::Linear | Interpolation::Ease => Vec::new(),
+        };
+        Curve { interpolation, frames, values, slopes }
+    }
+
+    /// The value at `frame`, which holds still before the first key and
+    /// after the last.
+    fn at(&self, frame: f64) -> f64 {
+        let n = self.frames.len();
+        if frame <= self.frames[0] {
+            return self.values[0]
+        }
+        if frame >= self.frames[n - 1] {
+            return self.values[n - 1]
+        }
+        let k = self.frames.windows(2).position(|pair| frame < pair[1]).unwrap();
+        let t = (frame - self.frames[k]) / (self.frames[k + 1] - self.frames[k]);
+        let (from, to) = (self.values[k], self.values[k + 1]);
+        match self.interpolation {
+            Interpolation::Linear => from + (to - from) * t,
+            Interpolation::Ease => from + (to - from) * t * t * (3.0 - 2.0 * t),
+            Interpolation::CatmullRom | Interpolation::Monotonic => interpolate(frame, &self.frames, &self.values, &self.slopes),
+        }
+    }
+
+    /// The smallest value at any key.
+    fn lowest(&self) -> f64 {
+        self.values.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+}
+
+/// An animation of a scene by keyframes, made by `Keyframes::animate`.
+#[derive(Clone, Debug)]
+pub struct KeyframeAnimation {
+    /// The scene being animated. Anything not keyed stays as it is here.
+    pub start: RenderRequest,
+    pub frames: usize,
+    center: [Option<Curve>; 2],
+    /// The logarithm of the zoom.
+    zoom: Option<Curve>,
+    seed: [Option<Curve>; 2],
+    palette_offset: Option<Curve>,
+    iterations: Option<Curve>,
+    rotation: Option<Curve>,
+    power: Option<Curve>,
+    bailout: Option<Curve>,
+}
+
+impl Animation for KeyframeAnimation {
+    fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn at(&self, time: f64) -> RenderRequest {
+        let at = |curve: &Option<Curve>, value: f64| curve.as_ref().map_or(value, |curve| curve.at(time));
+        let (upper_left, lower_right) = (self.start.upper_left, self.start.lower_right);
+        let start_center = (upper_left + lower_right) / 2.0;
+        let center = Complex { re: at(&self.center[0], start_center.re), im: at(&self.center[1], start_center.im) };
+        let half = (lower_right - upper_left) / at(&self.zoom, 0.0).exp() / 2.0;
+        let mut request = self.start.clone().viewport(center - half, center + half);
+        if let Fractal::Julia(seed) = self.start.fractal {
+            request.fractal = Fractal::Julia(Complex { re: at(&self.seed[0], seed.re), im: at(&self.seed[1], seed.im) });
+        }
+        request.coloring.offset = at(&self.palette_offset, self.start.coloring.offset);
+        request.scheduler.limit = at(&self.iterations, self.start.scheduler.limit as f64).round().max(1.0) as usize;
+        request.rotation = at(&self.rotation, self.start.rotation);
+        // a spline may overshoot below the keys, where the power or the
+        // bailout would stop making sense
+        let floored = |curve: &Option<Curve>, value: f64| at(curve, value).max(curve.as_ref().map_or(value, Curve::lowest));
+        request.orbit.power = floored(&self.power, self.start.orbit.power);
+        request.orbit.bailout = floored(&self.bailout, self.start.orbit.bailout);
+        request
+    }
+}
+
+/// Palette cycling: a single escape field, colored again for every frame
+/// with the palette turned a little further round, which is far quicker than
+/// rendering every frame.
+#[derive(Clone, Debug)]
+pub struct Cycle {
+    pub request: RenderRequest,
+    /// The field rendered for `request`.
+    pub field: EscapeField,
+    pub frames: usize,
+    /// How many times the palette goes all the way round over the frames,
+    /// where a whole number loops seamlessly and a negative one turns the
+    /// other way.
+    pub cycles: f64,
+}
+
+impl Animation for Cycle {
+    fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn at(&self, time: f64) -> RenderRequest {
+        let mut request = self.request.clone();
+        // the frame after the last is the first again
+        request.coloring.offset += self.cycles * time / self.frames as f64;
+        request
+    }
+
+    /// The one field, whatever the frame.
+    fn field(&self, _: &RenderRequest) -> Cow<'_, EscapeField> {
+        Cow::Borrowed(&self.field)
+    }
+}
+
+/// A path through the parameter plane for the seed of a Julia set to
+/// follow.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SeedPath {
+    /// Counterclockwise round a circle, from its rightmost point.
+    Circle { center: Complex<f64>, radius: f64 },
+    /// Round the main cardioid of the Mandelbrot set shrunk by `r`, which
+    /// is its edge when `r` is 1 and inside it when less, from its cusp.
+    Cardioid(f64),
+    /// Straight from point to point, at a steady speed.
+    Polyline(Vec<Complex<f64>>),
+    /// Along a Catmull-Rom spline through the points.
+    Spline(Vec<Complex<f64>>),
+}
+
+pub const SEEDPATHS: &str = "circle=<re>,<im>,<radius>\ncardioid=<r>\npolyline=<re>,<im>;<re>,<im>;...\nspline=<re>,<im>;<re>,<im>;...\n";
+
+impl FromStr for SeedPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let bad = || format!("unknown seed path {}", s);
+        let (kind, value) = s.split_once('=').ok_or_else(bad)?;
+        let points = || value.split(';')
+            .map(|point| parse_complex(point.trim()).ok_or_else(|| format!("can't use {:?} as a point", point)))
+            .collect::<std::result::Result<Vec<_>, _>>();
+        let path = match &*kind.to_ascii_lowercase() {
+            "circle" => match value.split(',').map(f64::from_str).collect::<std::result::Result<Vec<_>, _>>().as_deref() {
+                Ok(&[re, im, radius]) => SeedPath::Circle { center: Complex { re, im }, radius },
+                _ => return Err(format!("a circle is <re>,<im>,<radius>, not {}", value)),
+            },
+            "cardioid" => SeedPath::Cardioid(value.parse().map_err(|_| format!("can't use {:?} as a radius", value))?),
+            "polyline" => SeedPath::Polyline(points()?),
+            "spline" => SeedPath::Spline(points()?),
+            _ => return Err(bad()),
+        };
+        path.check()?;
+        Ok(path)
+    }
+}
+
+impl SeedPath {
+    /// Whether the path ends where it starts, so an animation along it
+    /// loops.
+    pub fn is_closed(&self) -> bool {
+        match self {
+            SeedPath::Circle { .. } | SeedPath::Cardioid(_) => true,
+            SeedPath::Polyline(points) | SeedPath::Spline(points) => points.first() == points.last(),
+        }
+    }
+
+    /// The point `t` of the way along the path, from 0 at its start to 1 at
+    /// its end.
+    pub fn at(&self, t: f64) -> Complex<f64> {
+        let turn = Complex::from_polar(1.0, std::f64::consts::TAU * t);
+        match self {
+            SeedPath::Circle { center, radius } => center + turn * radius,
+            SeedPath::Cardioid(r) => turn * r / 2.0 - turn * turn * r * r / 4.0,
+            SeedPath::Polyline(points) | SeedPath::Spline(points) => {
+                let interpolation = if matches!(self, SeedPath::Spline(_)) { Interpolation::CatmullRom } else { Interpolation::Linear };
+                // measured along the chords, so the seed goes at much the
+                // same speed over long and short ones
+                let mut lengths = vec![0.0];
+                for pair in points.windows(2) {
+                    lengths.push(lengths[lengths.len() - 1] + (pair[1] - pair[0]).norm());
+                }
+                let x = t * lengths[lengths.len() - 1];
+                let curve = |part: fn(&Complex<f64>) -> f64| Curve::new(interpolation, lengths.clone(), points.iter().map(part).collect()).at(x);
+                Complex { re: curve(|p| p.re), im: curve(|p| p.im) }
+            }
+        }
+    }
+
+    fn check(&self) -> std::result::Result<(), String> {
+        match self {
+            SeedPath::Circle { center, radius } if !(center.norm().is_finite() && radius.is_finite()) => Err("the circle has to be finite".to_string()),
+            SeedPath::Cardioid(r) if !r.is_finite() => Err("the cardioid has to be finite".to_string()),
+            SeedPath::Polyline(points) | SeedPath::Spline(points) => {
+                if points.len() < 2 {
+                    return Err("a path needs at least two points".to_string());
+                }
+                match points.windows(2).find(|pair| pair[0] == pair[1]) {
+                    Some(pair) => Err(format!("{} comes twice in a row", pair[0])),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A Julia set whose seed moves along a path, one step a frame.
+#[derive(Clone, Debug)]
+pub struct SeedAnimation {
+    /// The first frame, which is of a Julia set. Everything but its seed is
+    /// the same for every frame.
+    pub start: RenderRequest,
+    pub path: SeedPath,
+    pub frames: usize,
+    /// A map of where the seed is, drawn in the corner of every frame.
+    pub inset: Option<Inset>,
+}
+
+impl Animation for SeedAnimation {
+    fn frames(&self) -> usize {
+        self.frames
+    }
+
+    fn at(&self, time: f64) -> RenderRequest {
+        let mut request = self.start.clone();
+        request.fractal = Fractal::Julia(self.path.at(self.progress(time)));
+        request
+    }
+
+    /// Color `field` as usual, then draw the inset on it.
+    fn colorize(&self, request: &RenderRequest, field: &EscapeField) -> Vec<f32> {
+        let mut pixels = request.colorize(field);
+        if let (Some(inset), Fractal::Julia(seed)) = (&self.inset, request.fractal) {
+            inset.draw(&mut pixels, request.bounds, request.coloring.channels(), seed);
+        }
+        pixels
+    }
+}
+
+impl SeedAnimation {
+    /// How far along the path the seed is at `time`, in frames. Round a
+    /// closed path, the frame after the last would be the first again.
+    fn progress(&self, time: f64) -> f64 {
+        match self.frames {
+            _ if self.path.is_closed() => time / self.frames as f64,
+            0 | 1 => 0.0,
+            frames => time / (frames - 1) as f64,
+        }
+    }
+}
+
+/// A small map of the Mandelbrot set, for the corner of a Julia set
+/// animation, with the path of the seed traced on it.
+#[derive(Clone, Debug)]
+pub struct Inset {
+    bounds: (usize, usize),
+    upper_left: Complex<f64>,
+    lower_right: Complex<f64>,
+    /// The colored map, in RGB.
+    pixels: Vec<f32>,
+}
+
+impl Inset {
+    /// A map `width` pixels across, showing all of the Mandelbrot set and
+    /// all of `path`, colored as `request` is.
+    pub fn new(width: usize, path: &SeedPath, request: &RenderRequest) -> Result<Inset> {
+        let trace: Vec<Complex<f64>> = (0..=INSET_TRACE_POINTS).map(|i| path.at(i as f64 / INSET_TRACE_POINTS as f64)).collect();
+        let (mut upper_left, mut lower_right): (Complex<f64>, Complex<f64>) = (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.0, im: -1.5 });
+        for point in trace.iter() {
+            upper_left = Complex { re: upper_left.re.min(point.re - 0.1), im: upper_left.im.max(point.im + 0.1) };
+            lower_right = Complex { re: lower_right.re.max(point.re + 0.1), im: lower_right.im.min(point.im - 0.1) };
+        }
+        let height = ((width as f64 * (upper_left.im - lower_right.im) / (lower_right.re - upper_left.re)).round() as usize).max(1);
+        let (upper_left, lower_right) = fit_viewport((width, height), upper_left, lower_right);
+
+        let mut map = RenderRequest::mandelbrot().size(width, height).viewport(upper_left, lower_right);
+        map.coloring = request.coloring.clone();
+        map.coloring.alpha = None;
+        // the seeds are traced on the plane as it is, not turned with the view
+        (map.orbit, map.scheduler) = (request.orbit, request.scheduler);
+        let pixels = map.colorize(&map.render_escape()?);
+        let mut inset = Inset { bounds: (width, height), upper_left, lower_right, pixels };
+        for point in trace {
+            if let Some(i) = inset.pixel(point) {
+                for value in inset.pixels[3 * i..3 * i + 3].iter_mut() {
+                    *value = (*value + 1.0) / 2.0;
+                }
+            }
+        }
+        Ok(inset)
+    }
+
+    /// The index of the pixel `point` is in, if it is on the map.
+    fn pixel(&self, point: Complex<f64>) -> Option<usize> {
+        let column = (point.re - self.upper_left.re) / (self.lower_right.re - self.upper_left.re) * self.bounds.0 as f64;
+        let row = (self.upper_left.im - point.im) / (self.upper_left.im - self.lower_right.im) * self.bounds.1 as f64;
+        if column < 0.0 || row < 0.0 || column >= self.bounds.0 as f64 || row >= self.bounds.1 as f64 {
+            return None
+        }
+        Some(row as usize * self.bounds.0 + column as usize)
+    }
+
+    /// Draw the map in the lower right corner of a frame of `pixels` with
+    /// `channels` channels, with a white border and a dot on `seed`.
+    pub fn draw(&self, pixels: &mut [f32], bounds: (usize, usize), channels: usize, seed: Complex<f64>) {
+        let margin = (bounds.0 / 64).max(1);
+        let (width, height) = self.bounds;
+        if width + 2 * margin > bounds.0 || height + 2 * margin > bounds.1 {
+            return
+        }
+        let (left, top) = (bounds.0 - margin - width, bounds.1 - margin - height);
+        let dot = self.pixel(seed).map(|i| (i % width, i / width));
+        let radius = (width / 40).max(1) as f64;
+        let mut set = |column: usize, row: usize, color: [f32; 3]| {
+            let at = (row * bounds.0 + column) * channels;
+            pixels[at..at + 3].copy_from_slice(&color);
+            if channels == 4 {
+                pixels[at + 3] = 1.0;
+            }
+        };
+        for row in top - 1..=top + height {
+            for column in left - 1..=left + width {
+                if row < top || row == top + height || column < left || column == left + width {
+                    set(column, row, [1.0; 3]);
+                    continue
+                }
+                let (x, y) = (column - left, row - top);
+                let color = match dot.map(|(dx, dy)| (x as f64 - dx as f64).hypot(y as f64 - dy as f64)) {
+                    Some(distance) if distance <= radius => [1.0; 3],
+                    Some(distance) if distance <= radius + 1.0 => [0.0; 3],
+                    _ => {
+                        let i = 3 * (y * width + x);
+                        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]]
+                    }
+                };
+                set(column, row, color);
+            }
+        }
+    }
+}
+
+/// The name of frame `index` in a sequence named by `pattern`, which holds a
+/// printf style `%d`, maybe zero padded as in `%05d`. `None` if there is no
+/// such placeholder.
+pub fn frame_filename(pattern: &str, index: usize) -> Option<String> {
+    let start = pattern.find('%')?;
+    let spec = &pattern[start + 1..];
+    let end = spec.find('d')?;
+    let width = &spec[..end];
+    if !width.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+    let number = match width.strip_prefix('0') {
+        Some(width) => format!("{:0width$}", index, width = width.parse().unwrap_or(0)),
+        None => format!("{:width$}", index, width = width.parse().unwrap_or(0)),
+    };
+    Some(format!("{}{}{}", &pattern[..start], number, &spec[end + 1..]))
+}
+
+/// The kinds of video `VideoWriter` writes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 with full resolution chroma, which carries its own size and
+    /// frame rate, for piping to an encoder.
+    Y4m,
+    /// Bare 8-bit RGB frames, one after another.
+    Raw,
+    /// An animated GIF, with every frame cut down to its own palette of 256
+    /// colors and any alpha channel to on or off.
+    Gif,
+    /// An animated PNG, which keeps every color and the alpha channel.
+    Apng,
+}
+
+pub const VIDEOFORMATS: &str = "y4m (default)\nraw (rgb24)\ngif\napng\n";
+
+impl FromStr for VideoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "y4m" => Ok(VideoFormat::Y4m),
+            "raw" | "rgb24" => Ok(VideoFormat::Raw),
+            "gif" => Ok(VideoFormat::Gif),
+            "apng" | "png" => Ok(VideoFormat::Apng),
+            _ => Err(format!("unknown video format {}", s)),
+        }
+    }
+}
+
+impl VideoFormat {
+    /// Guess the format from the extension of `filename`.
+    pub fn from_filename(filename: &str) -> Option<VideoFormat> {
+        Path::new(filename).extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn supports_alpha(self) -> bool {
+        matches!(self, VideoFormat::Gif | VideoFormat::Apng)
+    }
+}
+
+/// How a `VideoWriter` encodes its frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct VideoOptions {
+    pub format: VideoFormat,
+    pub fps: u32,
+    /// How many times an animated GIF or PNG plays, or 0 to loop forever.
+    pub loops: u16,
+    /// Dither GIF frames when cutting them down to their palettes, which
+    /// hides banding at the cost of a noisier, larger file.
+    pub dither: bool,
+    /// How many frames there will be, which an animated PNG has to say
+    /// before the first.
+    pub frames: usize,
+}
+
+enum Encoder<W: Write> {
+    Stream(W),
+    Gif(GifEncoder<W>),
+    Apng(png::Writer<W>),
+}
+
+/// Writes frames of colored pixels, as they come from `colorize`, as a
+/// video.
+pub struct VideoWriter<W: Write> {
+    encoder: Encoder<W>,
+    options: VideoOptions,
+    bounds: (usize, usize),
+    /// Frames written so far.
+    written: usize,
+}
+
+impl<W: Write> VideoWriter<W> {
+    /// Start a video of `bounds` sized frames, writing its header.
+    pub fn new(mut output: W, bounds: (usize, usize), options: &VideoOptions) -> io::Result<VideoWriter<W>> {
+        let too_big = || io::Error::new(io::ErrorKind::InvalidInput, format!("{}x{} frames are too big for {:?}", bounds.0, bounds.1, options.format));
+        let encoder = match options.format {
+            VideoFormat::Y4m => {
+                // limited range BT.601 is what players assume of y4m
+                writeln!(output, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444 XCOLORRANGE=LIMITED", bounds.0, bounds.1, options.fps)?;
+                Encoder::Stream(output)
+            }
+            VideoFormat::Raw => Encoder::Stream(output),
+            VideoFormat::Gif => {
+                u16::try_from(bounds.0).and(u16::try_from(bounds.1)).map_err(|_| too_big())?;
+                let mut encoder = GifEncoder::new_with_speed(output, GIF_QUANTIZER_SPEED);
+                let repeat = if options.loops == 0 { Repeat::Infinite } else { Repeat::Finite(options.loops - 1) };
+                encoder.set_repeat(repeat).map_err(image_error)?;
+                Encoder::Gif(encoder)
+            }
+            VideoFormat::Apng => {
+                let (width, height) = (u32::try_from(bounds.0).map_err(|_| too_big())?, u32::try_from(bounds.1).map_err(|_| too_big())?);
+                let fps = u16::try_from(options.fps).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "animated png can't go above 65535 fps"))?;
+                let mut encoder = png::Encoder::new(output, width, height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(options.frames as u32, options.loops as u32)?;
+                encoder.set_frame_delay(1, fps)?;
+                Encoder::Apng(encoder.write_header()?)
+            }
+        };
+        Ok(VideoWriter { encoder, options: *options, bounds, written: 0 })
+    }
+
+    /// Write a frame of `pixels` with `channels` channels each. Any alpha
+    /// channel is dropped unless the format has one.
+    pub fn write_frame(&mut self, pixels: &[f32], channels: usize) -> io::Result<()> {
+        assert!(pixels.len() == self.bounds.0 * self.bounds.1 * channels);
+        let index = self.written;
+        self.written += 1;
+        let rgb = pixels.chunks(channels).map(|pixel| [pixel[0], pixel[1], pixel[2]].map(|v| v.clamp(0.0, 1.0)));
+        match (&mut self.encoder, self.options.format) {
+            (Encoder::Stream(output), VideoFormat::Raw) => {
+                let bytes: Vec<u8> = rgb.flat_map(|pixel| pixel.map(|v| (v * 255.0).round() as u8)).collect();
+                output.write_all(&bytes)
+            }
+            (Encoder::Stream(output), _) => {
+                let len = self.bounds.0 * self.bounds.1;
+                let mut planes = vec![0; 3 * len];
+                for (i, [r, g, b]) in rgb.enumerate() {
+                    planes[i] = (16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8;
+                    planes[len + i] = (128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8;
+                    planes[2 * len + i] = (128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8;
+                }
+                output.write_all(b"FRAME\n")?;
+                output.write_all(&planes)
+            }
+            (Encoder::Gif(encoder), _) => {
+                let image = gif_colors(to_image(pixels, self.bounds, channels, Depth::Eight).into_rgba8(), self.options.dither);
+                // delays are in whole hundredths of a second, so they are
+                // rounded to keep the frames on time on average
+                let at = |index: usize| (index as f64 * 100.0 / self.options.fps as f64).round() as u32;
+                let delay = Delay::from_numer_denom_ms(10 * (at(index + 1) - at(index)), 1);
+                encoder.encode_frame(Frame::from_parts(image, 0, 0, delay)).map_err(image_error)
+            }
+            (Encoder::Apng(writer), _) => {
+                let image = to_image(pixels, self.bounds, channels, Depth::Eight).into_rgba8();
+                Ok(writer.write_image_data(image.as_raw())?)
+            }
+        }
+    }
+
+    /// Finish the video. An animated PNG has to have had as many frames as
+    /// it was told it would. A GIF's encoder keeps its output, writing the
+    /// end of the file as it is dropped here, so to hear of errors flushing
+    /// it, lend the writer and flush it afterwards.
+    pub fn finish(self) -> io::Result<()> {
+        match self.encoder {
+            Encoder::Stream(mut output) => output.flush(),
+            Encoder::Gif(encoder) => {
+                drop(encoder);
+                Ok(())
+            }
+            Encoder::Apng(writer) => Ok(writer.finish()?),
+        }
+    }
+}
+
+/// Cut `image` down to a palette of its own, of 256 colors or of 255 and a
+/// transparent one for the pixels that are more than half see-through.
+///
+/// The GIF encoder would quantize a frame with more colors itself, but it
+/// can't dither, and it picks the transparent color from the same palette
+/// as the rest, which can make opaque pixels of that color see-through.
+/// Given a frame with no more than 256 colors, it keeps them as they are.
+fn gif_colors(mut image: RgbaImage, dither: bool) -> RgbaImage {
+    let transparent: Vec<bool> = image.pixels().map(|pixel| pixel.0[3] < 128).collect();
+    let has_transparent = transparent.iter().any(|&t| t);
+    let opaque: Vec<u8> = image.pixels().zip(transparent.iter())
+        .filter(|(_, &t)| !t)
+        .flat_map(|(pixel, _)| [pixel.0[0], pixel.0[1], pixel.0[2], 255])
+        .collect();
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = 255;
+    }
+    let colors = if has_transparent { 255 } else { 256 };
+    let quantizer = NeuQuant::new(GIF_QUANTIZER_SPEED, colors, if opaque.is_empty() { &[0, 0, 0, 255] } else { &opaque });
+    if dither {
+        imageops::dither(&mut image, &quantizer);
+    }
+    for (pixel, &t) in image.pixels_mut().zip(transparent.iter()) {
+        if t {
+            pixel.0 = [0; 4];
+        } else if !dither {
+            quantizer.map_pixel(&mut pixel.0);
+        }
+    }
+    image
+}
+
+fn image_error(error: ImageError) -> io::Error {
+    match error {
+        ImageError::IoError(error) => error,
+        error => io::Error::new(io::ErrorKind::InvalidInput, error),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::codecs::gif::GifDecoder;
+    use image::{AnimationDecoder, ImageDecoder};
+    use crate::fractal::DEFAULT_SEED;
+
+    fn zoom() -> Zoom {
+        Zoom {
+            start: RenderRequest::mandelbrot().size(40, 30).viewport(Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 }),
+            target: Complex { re: -0.75, im: 0.1 },
+            depth: 1000.0,
+            frames: 4,
+            iteration_growth: 100.0,
+        }
+    }
+
+    #[test]
+    fn test_zoom_frames() {
+        let zoom = zoom();
+        let first = zoom.frame(0);
+        assert_eq!((first.upper_left, first.lower_right), (zoom.start.upper_left, zoom.start.lower_right));
+        assert_eq!(first.scheduler.limit, 255);
+
+        // each frame is ten times narrower than the one before
+        for index in 1..4 {
+            let (last, frame) = (zoom.frame(index - 1), zoom.frame(index));
+            let ratio = (last.lower_right.re - last.upper_left.re) / (frame.lower_right.re - frame.upper_left.re);
+            assert!((ratio - 10.0).abs() < 1e-9);
+            assert_eq!(frame.scheduler.limit, 255 + 100 * index);
+        }
+        let last = zoom.frame(3);
+        let center = (last.upper_left + last.lower_right) / 2.0;
+        assert!((center - zoom.target).norm() < 1e-12);
+        assert!((last.upper_left.im - last.lower_right.im - 0.003).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_render_in_order() {
+        let mut seen = Vec::new();
+        zoom().render(|index, request, field, pixels| {
+            assert_eq!(field.upper_left, request.upper_left);
+            assert_eq!(pixels.len(), 40 * 30 * 3);
+            seen.push(index);
+            Ok(())
+        }).unwrap();
+        assert_eq!(seen, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_render_batched() {
+        // small frames are rendered several at once on the pool, which
+        // needs more than one thread to go wrong, as each frame's tiles wait
+        // for one; the global pool has them unless RAYON_NUM_THREADS says
+        // otherwise
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(4).build_global();
+        let zoom = Zoom { start: zoom().start.size(64, 48), frames: 3 * rayon::current_num_threads(), ..zoom() };
+        let blur = MotionBlur { samples: 2, shutter: 180.0 };
+        for blur in [None, Some(&blur)] {
+            let mut seen = 0;
+            render_frames(&zoom, blur, |index, request, field, _| {
+                assert_eq!(index, seen);
+                // with blur the field is that of a moment beside the frame
+                if blur.is_none() {
+                    let mut expected = request.field();
+                    request.scheduler.render(&mut expected, request.fractal, request.orbit, request.rotation, |_, _| ());
+                    assert_eq!(field.samples, expected.samples);
+                }
+                seen += 1;
+                Ok(())
+            }).unwrap();
+            assert_eq!(seen, zoom.frames);
+        }
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        let blur = MotionBlur { samples: 4, shutter: 180.0 };
+        assert_eq!(blur.times(2), vec![1.8125, 1.9375, 2.0625, 2.1875]);
+        assert_eq!(MotionBlur { samples: 1, shutter: 360.0 }.times(5), vec![5.0]);
+
+        // black and white make the gray of half the light, and transparent
+        // moments only thin the alpha
+        let blended = blend([&[0.0, 0.0, 0.0][..], &[1.0, 1.0, 1.0][..]].into_iter(), 3);
+        assert!((blended[0] - linear_to_srgb(0.5)).abs() < 1e-6);
+        let blended = blend([&[0.2, 0.4, 0.6, 1.0][..], &[0.9, 0.9, 0.9, 0.0][..]].into_iter(), 4);
+        assert!((blended[1] - 0.4).abs() < 1e-6);
+        assert_eq!(blended[3], 0.5);
+
+        let request = zoom().start;
+        let cycle = Cycle { field: request.render_escape().unwrap(), request, frames: 4, cycles: 1.0 };
+        let mut frames = Vec::new();
+        render_frames(&cycle, Some(&MotionBlur { samples: 1, shutter: 180.0 }), |_, _, _, pixels| {
+            frames.push(pixels);
+            Ok(())
+        }).unwrap();
+        render_frames(&cycle, Some(&blur), |index, request, field, pixels| {
+            assert_eq!(request.coloring.offset, cycle.frame(index).coloring.offset);
+            assert!(std::ptr::eq(field, &cycle.field));
+            assert_eq!(pixels.len(), frames[index].len());
+            assert_ne!(pixels, frames[index]);
+            Ok(())
+        }).unwrap();
+    }
+
+    #[test]
+    fn test_cycle() {
+        let request = zoom().start.palette_offset(0.25);
+        let cycle = Cycle { field: request.render_escape().unwrap(), request, frames: 4, cycles: -2.0 };
+        let offsets: Vec<f64> = (0..4).map(|index| cycle.frame(index).coloring.offset).collect();
+        assert_eq!(offsets, vec![0.25, -0.25, -0.75, -1.25]);
+
+        let mut frames = Vec::new();
+        cycle.render(|index, request, field, pixels| {
+            assert_eq!(request.coloring.offset, offsets[index]);
+            assert!(std::ptr::eq(field, &cycle.field));
+            frames.push(pixels);
+            Ok(())
+        }).unwrap();
+        assert_eq!(frames.len(), 4);
+        // half a turn apart, then whole turns apart
+        assert_ne!(frames[0], frames[1]);
+        let same = frames[0].iter().zip(frames[2].iter()).filter(|(a, b)| a == b).count();
+        assert!(same > frames[0].len() * 99 / 100);
+    }
+
+    #[test]
+    fn test_seed_paths() {
+        let close = |a: Complex<f64>, b: Complex<f64>| (a - b).norm() < 1e-12;
+        let circle: SeedPath = "circle=-0.75,0,0.1".parse().unwrap();
+        assert!(close(circle.at(0.0), Complex { re: -0.65, im: 0.0 }));
+        assert!(close(circle.at(0.25), Complex { re: -0.75, im: 0.1 }));
+        // the cusp, and where the cardioid meets the period 2 bulb
+        let cardioid: SeedPath = "cardioid=1".parse().unwrap();
+        assert!(close(cardioid.at(0.0), Complex { re: 0.25, im: 0.0 }));
+        assert!(close(cardioid.at(0.5), Complex { re: -0.75, im: 0.0 }));
+
+        let polyline: SeedPath = "polyline=0,0;3,0;3,1".parse().unwrap();
+        assert!(!polyline.is_closed());
+        assert!(close(polyline.at(0.5), Complex { re: 2.0, im: 0.0 }));
+        assert!(close(polyline.at(0.875), Complex { re: 3.0, im: 0.5 }));
+        let spline: SeedPath = "spline=-0.8,0.156; -0.7,0.27; -0.4,0.6; -0.8,0.156".parse().unwrap();
+        assert!(spline.is_closed());
+        assert!(close(spline.at(0.0), Complex { re: -0.8, im: 0.156 }));
+        assert!(close(spline.at(1.0), Complex { re: -0.8, im: 0.156 }));
+
+        for bad in ["circle=0,0", "cardioid=x", "polyline=0,0", "spline=0,0;0,0;1,1", "figure8=1"] {
+            assert!(bad.parse::<SeedPath>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_seed_animation() {
+        let path: SeedPath = "cardioid=0.9".parse().unwrap();
+        let start = RenderRequest::julia(DEFAULT_SEED).size(64, 48).iterations(50);
+        let inset = Inset::new(16, &path, &start).unwrap();
+        let animation = SeedAnimation { start, path: path.clone(), frames: 4, inset: Some(inset) };
+        assert_eq!(animation.frame(2).fractal, Fractal::Julia(path.at(0.5)));
+
+        animation.render(|index, request, field, pixels| {
+            assert_eq!(request.fractal, Fractal::Julia(path.at(index as f64 / 4.0)));
+            // the inset has a white border, and the frame outside it is as
+            // it was colored
+            let at = |column: usize, row: usize| &pixels[3 * (row * 64 + column)..3 * (row * 64 + column) + 3];
+            assert_eq!(at(63 - 1 - 16, 47 - 1), [1.0; 3]);
+            assert_eq!(at(10, 10), &request.colorize(field)[3 * (10 * 64 + 10)..3 * (10 * 64 + 10) + 3]);
+            Ok(())
+        }).unwrap();
+
+        let open = SeedAnimation { path: "polyline=0,0;1,0".parse().unwrap(), inset: None, ..animation };
+        assert_eq!(open.frame(3).fractal, Fractal::Julia(Complex { re: 1.0, im: 0.0 }));
+    }
+
+    #[test]
+    fn test_keyframes() {
+        let keyframes = Keyframes::from_toml(r#"
+            interpolation = "linear"
+
+            [[keyframe]]
+            frame = 2
+            center = [0.0, 0.0]
+            zoom = 1.0
+            seed = [-0.8, 0.156]
+            iterations = 255
+            rotation = 0.0
+
+            [[keyframe]]
+            frame = 6
+            zoom = 100.0
+            palette_offset = 0.5
+
+            [[keyframe]]
+            frame = 10
+            center = [-1.0, 0.5]
+            iterations = 1255
+            rotation = 80.0
+            power = 3.0
+            bailout = 4.0
+        "#).unwrap();
+        let start = RenderRequest::julia(Complex { re: 0.0, im: 0.0 }).size(40, 30);
+        let animation = keyframes.animate(start, None).unwrap();
+        assert_eq!(animation.frames, 11);
+
+        // everything holds still before the first key that sets it
+        let first = animation.frame(0);
+        assert_eq!((first.upper_left, first.lower_right), (Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 }));
+        assert_eq!(first.fractal, Fractal::Julia(Complex { re: -0.8, im: 0.156 }));
+        assert_eq!((first.coloring.offset, first.scheduler.limit), (0.5, 255));
+
+        // the zoom goes by equal factors, and the center and iterations evenly
+        let frame = animation.frame(4);
+        assert!(((frame.lower_right.re - frame.upper_left.re) - 0.4).abs() < 1e-12);
+        assert!(((frame.upper_left + frame.lower_right) / 2.0 - Complex { re: -0.25, im: 0.125 }).norm() < 1e-12);
+        let frame = animation.frame(8);
+        let center = (frame.upper_left + frame.lower_right) / 2.0;
+        assert!((center - Complex { re: -0.75, im: 0.375 }).norm() < 1e-12);
+        assert!(((frame.lower_right.re - frame.upper_left.re) - 0.04).abs() < 1e-12);
+        assert_eq!(frame.scheduler.limit, 1005);
+        assert!((frame.rotation - 60.0).abs() < 1e-12);
+        assert!((frame.orbit.power - 3.0).abs() < 1e-12 && (frame.orbit.bailout - 4.0).abs() < 1e-12);
+        assert_eq!((first.rotation, first.orbit.power, first.orbit.bailout), (0.0, 3.0, 4.0));
+
+        assert_eq!(keyframes.animate(RenderRequest::julia(DEFAULT_SEED), Some(20)).unwrap().frames, 20);
+    }
+
+    #[test]
+    fn test_interpolations() {
+        let keys: Vec<Keyframe> = [(0, 0.0), (10, 1.0), (20, 1.0), (30, 3.0)].iter()
+            .map(|&(frame, offset)| Keyframe { frame, palette_offset: Some(offset), ..Keyframe::default() })
+            .collect();
+        let value = |key: &Keyframe| key.palette_offset;
+        for interpolation in [Interpolation::Linear, Interpolation::Ease, Interpolation::CatmullRom, Interpolation::Monotonic] {
+            let curve = Curve::keyed(interpolation, &keys, &value).unwrap();
+            for key in keys.iter() {
+                assert!((curve.at(key.frame as f64) - key.palette_offset.unwrap()).abs() < 1e-12, "{:?}", interpolation);
+            }
+            assert_eq!(curve.at(-5.0), 0.0);
+            assert_eq!(curve.at(35.0), 3.0);
+        }
+        let at = |interpolation, frame| Curve::keyed(interpolation, &keys, &value).unwrap().at(frame);
+        assert!((at(Interpolation::Linear, 5.0) - 0.5).abs() < 1e-12);
+        assert!((at(Interpolation::Ease, 2.0) - 0.104).abs() < 1e-12);
+        // catmull-rom overshoots the flat stretch, which the monotonic spline
+        // keeps to
+        assert!(at(Interpolation::CatmullRom, 15.0) < 1.0);
+        assert!((10..=20).all(|frame| at(Interpolation::Monotonic, frame as f64) == 1.0));
+        assert!((1..30).all(|frame| at(Interpolation::Monotonic, frame as f64) >= at(Interpolation::Monotonic, frame as f64 - 1.0)));
+    }
+
+    #[test]
+    fn test_bad_keyframes() {
+        let bad = |toml: &str| matches!(Keyframes::from_toml(toml).and_then(|keys| keys.animate(RenderRequest::mandelbrot(), None)),
+                                         Err(Error::BadKeyframes(_)));
+        assert!(bad("keyframe = []"));
+        assert!(bad("[[keyframe]]\nframe = 3\n[[keyframe]]\nframe = 3"));
+        assert!(bad("[[keyframe]]\nframe = 0\nzoom = 0.0"));
+        assert!(bad("[[keyframe]]\nframe = 0\nseed = [0.0, 0.5]"));
+        assert!(bad("[[keyframe]]\nframe = 0\nturn = 90.0"));
+        assert!(bad("[[keyframe]]\nframe = 0\npower = 1.0"));
+        assert!(bad("[[keyframe]]\nframe = 0\nbailout = 0.5"));
+        assert!(bad("[[keyframe]]\nframe = 0\nrotation = nan"));
+        assert!(!bad("[[keyframe]]\nframe = 0\nrotation = 90.0\npower = 3.0\nbailout = 10.0"));
+        assert!(!bad("[[keyframe]]\nframe = 0\nzoom = 2.0"));
+    }
+
+    #[test]
+    fn test_frame_filename() {
+        assert_eq!(frame_filename("zoom-%05d.png", 42).as_deref(), Some("zoom-00042.png"));
+        assert_eq!(frame_filename("frames/%d.tiff", 7).as_deref(), Some("frames/7.tiff"));
+        assert_eq!(frame_filename("zoom.y4m", 7), None);
+        assert_eq!(frame_filename("100%.png", 7), None);
+    }
+
+    fn video(format: VideoFormat, frames: &[&[f32]], bounds: (usize, usize), channels: usize) -> Vec<u8> {
+        let options = VideoOptions { format, fps: 24, loops: 0, dither: false, frames: frames.len() };
+        let mut bytes = Vec::new();
+        let mut video = VideoWriter::new(&mut bytes, bounds, &options).unwrap();
+        for pixels in frames {
+            video.write_frame(pixels, channels).unwrap();
+        }
+        video.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_video() {
+        let pixels: &[f32] = &[1.0, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0, 1.0];
+        assert_eq!(video(VideoFormat::Raw, &[pixels], (2, 1), 4), vec![255, 255, 255, 0, 0, 0]);
+
+        let y4m = video(VideoFormat::Y4m, &[pixels, pixels], (2, 1), 4);
+        let header = b"YUV4MPEG2 W2 H1 F24:1 Ip A1:1 C444 XCOLORRANGE=LIMITED\n";
+        assert_eq!(&y4m[..header.len()], header);
+        assert_eq!(&y4m[header.len()..], b"FRAME\n\xeb\x10\x80\x80\x80\x80FRAME\n\xeb\x10\x80\x80\x80\x80");
+        assert_eq!(VideoFormat::from_filename("loop.GIF"), Some(VideoFormat::Gif));
+        assert_eq!(VideoFormat::from_filename("loop.png"), Some(VideoFormat::Apng));
+        assert_eq!(VideoFormat::from_filename("loop.mp4"), None);
+    }
+
+    #[test]
+    fn test_animated_gif() {
+        // a gradient with more colors than a gif palette holds, the last
+        // frame with a see-through corner
+        let gradient: Vec<f32> = (0..32 * 32).flat_map(|i| [(i % 32) as f32 / 31.0, (i / 32) as f32 / 31.0, 0.5, 1.0]).collect();
+        let mut corner = gradient.clone();
+        corner[3] = 0.0;
+        let gif = video(VideoFormat::Gif, &[&gradient, &gradient, &corner], (32, 32), 4);
+
+        let decoder = GifDecoder::new(&gif[..]).unwrap();
+        assert_eq!(decoder.dimensions(), (32, 32));
+        let mut delays = Vec::new();
+        let expected = to_image(&gradient, (32, 32), 4, Depth::Eight).into_rgba8();
+        for frame in decoder.into_frames() {
+            let frame = frame.unwrap();
+            delays.push(frame.delay().numer_denom_ms());
+            let buffer = frame.into_buffer().into_raw();
+            let error: i32 = buffer.iter().zip(expected.as_raw()).skip(4).map(|(&a, &b)| (a as i32 - b as i32).abs()).sum();
+            assert!(error < 12 * 32 * 32 * 3, "off by {} a pixel", error / (32 * 32));
+            assert_eq!(buffer[3], if delays.len() == 3 { 0 } else { 255 });
+        }
+        // 24 fps is 4.17 hundredths of a second a frame
+        assert_eq!(delays, vec![(40, 1), (40, 1), (50, 1)]);
+    }
+
+    #[test]
+    fn test_animated_png() {
+        let frames: Vec<Vec<f32>> = (0..3).map(|i| vec![i as f32 / 2.0; 2 * 2 * 3]).collect();
+        let apng = video(VideoFormat::Apng, &frames.iter().map(Vec::as_slice).collect::<Vec<_>>(), (2, 2), 3);
+
+        let mut reader = png::Decoder::new(&apng[..]).read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (3, 0));
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        for value in [0, 128, 255] {
+            reader.next_frame(&mut buffer).unwrap();
+            assert_eq!(&buffer[..4], &[value, value, value, 255]);
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+use num::Complex;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::color::{linear_to_srgb, srgb_to_linear, Coloring, FieldStats};
+use crate::escape::EscapeField;
+use crate::scheduler::Scheduler;
+use crate::fractal::{view_point, Fractal, Orbit};
+
+/// Where the extra samples for a pixel are placed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pattern {
+    /// A regular square grid.
+    Grid,
+    /// A square grid turned by atan(1/2), so no two samples share a row or
+    /// column.
+    #[default]
+    #[serde(rename = "rotated")]
+    RotatedGrid,
+    /// One random sample in each cell of a square grid.
+    Jittered,
+    /// Random samples kept apart from each other, which trades aliasing for
+    /// fine, even noise.
+    BlueNoise,
+}
+
+pub const PATTERNS: &str = "grid\nrotated (default)\njittered\nbluenoise\n";
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "grid" => Ok(Pattern::Grid),
+            "rotated" => Ok(Pattern::RotatedGrid),
+            "jittered" => Ok(Pattern::Jittered),
+            "bluenoise" => Ok(Pattern::BlueNoise),
+            _ => Err(format!("unknown sample pattern {}", s)),
+        }
+    }
+}
+
+/// How the samples around a pixel are weighted when they are combined.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Every sample inside the pixel counts the same.
+    Box,
+    /// Weight falls off linearly, reaching zero one pixel away.
+    #[default]
+    Tent,
+    /// A two-lobed Lanczos window, the sharpest of the three.
+    Lanczos,
+}
+
+pub const FILTERS: &str = "box\ntent (default)\nlanczos\n";
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "box" => Ok(Filter::Box),
+            "tent" => Ok(Filter::Tent),
+            "lanczos" => Ok(Filter::Lanczos),
+            _ => Err(format!("unknown reconstruction filter {}", s)),
+        }
+    }
+}
+
+impl Filter {
+    /// How far from the pixel, in pixels, samples are taken.
+    pub fn radius(self) -> f64 {
+        match self {
+            Filter::Box => 0.5,
+            Filter::Tent => 1.0,
+            Filter::Lanczos => 2.0,
+        }
+    }
+
+    /// The weight of a sample `x` pixels away along one axis.
+    pub fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        if x >= self.radius() {
+            return 0.0
+        }
+        match self {
+            Filter::Box => 1.0,
+            Filter::Tent => 1.0 - x,
+            Filter::Lanczos => sinc(x) * sinc(x / 2.0),
+        }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Settings for supersampling.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Antialias {
+    /// Samples per pixel. Grid patterns round this up to a square number.
+    pub samples: usize,
+    #[serde(default)]
+    pub pattern: Pattern,
+    #[serde(default)]
+    pub filter: Filter,
+    /// Only pixels that differ from a neighbour by more than this much, in
+    /// any channel, are supersampled. Zero supersamples every pixel.
+    #[serde(default = "default_threshold", serialize_with = "serialize_threshold")]
+    pub threshold: f32,
+}
+
+pub const DEFAULT_THRESHOLD: f32 = 0.05;
+
+fn default_threshold() -> f32 {
+    DEFAULT_THRESHOLD
+}
+
+/// Write the threshold as the shortest decimal that reads back the same, as
+/// some formats would otherwise widen 0.05 to 0.05000000074505806.
+fn serialize_threshold<S: Serializer>(threshold: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(threshold.to_string().parse().unwrap())
+}
+
+/// A small, fast random number generator (splitmix64), so that patterns are
+/// the same from run to run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Place `n` samples in the unit square according to `pattern`. `seed` picks
+/// the random numbers for the random patterns.
+pub fn sample_offsets(pattern: Pattern, n: usize, seed: u64) -> Vec<(f64, f64)> {
+    let side = (n as f64).sqrt().ceil() as usize;
+    let cell = 1.0 / side as f64;
+    let mut rng = Rng(seed);
+    match pattern {
+        Pattern::Grid => (0..side * side)
+            .map(|i| (((i % side) as f64 + 0.5) * cell, ((i / side) as f64 + 0.5) * cell))
+            .collect(),
+        Pattern::RotatedGrid => {
+            let (sin, cos) = 0.5f64.atan().sin_cos();
+            (0..side * side)
+                .map(|i| {
+                    let (x, y) = (((i % side) as f64 + 0.5) * cell - 0.5, ((i / side) as f64 + 0.5) * cell - 0.5);
+                    ((x * cos - y * sin + 0.5).rem_euclid(1.0), (x * sin + y * cos + 0.5).rem_euclid(1.0))
+                })
+                .collect()
+        }
+        Pattern::Jittered => (0..side * side)
+            .map(|i| (((i % side) as f64 + rng.next()) * cell, ((i / side) as f64 + rng.next()) * cell))
+            .collect(),
+        Pattern::BlueNoise => {
+            // Mitchell's best candidate: of several random candidates, keep
+            // the one furthest from the samples placed so far, measuring
+            // distance on a torus so the pattern tiles
+            let mut points: Vec<(f64, f64)> = Vec::with_capacity(n);
+            for i in 0..n {
+                let candidates = 1 + 10 * i;
+                let mut best = (0.0, 0.0);
+                let mut best_dist = -1.0;
+                for _ in 0..candidates {
+                    let candidate = (rng.next(), rng.next());
+                    let dist = points.iter()
+                        .map(|&p| toroidal_dist_sqr(p, candidate))
+                        .fold(f64::INFINITY, f64::min);
+                    if dist > best_dist {
+                        best = candidate;
+                        best_dist = dist;
+                    }
+                }
+                points.push(best);
+            }
+            points
+        }
+    }
+}
+
+fn toroidal_dist_sqr(a: (f64, f64), b: (f64, f64)) -> f64 {
+    let dx = (a.0 - b.0).abs();
+    let dy = (a.1 - b.1).abs();
+    dx.min(1.0 - dx).powi(2) + dy.min(1.0 - dy).powi(2)
+}
+
+/// Find the pixels that differ from a neighbour by more than `threshold` in
+/// some channel.
+fn find_edges(pixels: &[f32], bounds: (usize, usize), channels: usize, threshold: f32) -> Vec<usize> {
+    let differs = |a: usize, b: usize| {
+        (0..channels).any(|c| (pixels[a * channels + c] - pixels[b * channels + c]).abs() > threshold)
+    };
+    let mut marked = vec![threshold <= 0.0; bounds.0 * bounds.1];
+    for row in 0..bounds.1 {
+        for col in 0..bounds.0 {
+            let i = row * bounds.0 + col;
+            if col + 1 < bounds.0 && differs(i, i + 1) {
+                marked[i] = true;
+                marked[i + 1] = true;
+            }
+            if row + 1 < bounds.1 && differs(i, i + bounds.0) {
+                marked[i] = true;
+                marked[i + bounds.0] = true;
+            }
+        }
+    }
+    marked.iter().enumerate().filter(|(_, &m)| m).map(|(i, _)| i).collect()
+}
+
+/// Where a field holding a band of rows of a taller image sits in that image,
+/// so that its supersamples land exactly where they would in the whole image.
+#[derive(Copy, Clone, Debug)]
+pub struct Band {
+    pub first_row: usize,
+    pub image_bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// How far the image is turned counterclockwise, in degrees.
+    pub rotation: f64,
+}
+
+/// Replace the edge pixels of `pixels`, which was colored from `field`, with
+/// filtered supersamples of `fractal`, placed and iterated like the field by
+/// `scheduler`, `orbit` and `rotation`. Colors are averaged in linear light,
+/// weighted by alpha so transparent samples don't darken their neighbours.
+#[allow(clippy::too_many_arguments)]
+pub fn antialias(pixels: &mut [f32],
+                 field: &EscapeField,
+                 coloring: &Coloring,
+                 fractal: Fractal,
+                 scheduler: &Scheduler,
+                 orbit: Orbit,
+                 rotation: f64,
+                 settings: &Antialias) {
+    let whole = Band { first_row: 0, image_bounds: field.bounds, upper_left: field.upper_left, lower_right: field.lower_right, rotation };
+    antialias_band(pixels, field, &whole, coloring, fractal, scheduler, orbit, settings);
+}
+
+/// `antialias` for a `field` that is just `band` of a bigger image.
+#[allow(clippy::too_many_arguments)]
+pub fn antialias_band(pixels: &mut [f32],
+                      field: &EscapeField,
+                      band: &Band,
+                      coloring: &Coloring,
+                      fractal: Fractal,
+                      scheduler: &Scheduler,
+                      orbit: Orbit,
+                      settings: &Antialias) {
+    let bounds = field.bounds;
+    let channels = coloring.channels();
+    let stats = FieldStats::of(field);
+    let radius = settings.filter.radius();
+
+    // the random patterns get a fresh toroidal shift for every pixel, so
+    // that their noise doesn't repeat in a visible grid
+    let offsets = sample_offsets(settings.pattern, settings.samples, 0);
+    let shifted = matches!(settings.pattern, Pattern::Jittered | Pattern::BlueNoise);
+
+    let edges = find_edges(pixels, bounds, channels, settings.threshold);
+    let resampled: Vec<(usize, [f32; 4])> = edges.par_iter().map(|&i| {
+        let (col, row) = ((i % bounds.0) as f64, (band.first_row + i / bounds.0) as f64);
+        let shift = if shifted {
+            let mut rng = Rng((band.first_row * bounds.0 + i) as u64);
+            (rng.next(), rng.next())
+        } else {
+            (0.0, 0.0)
+        };
+        let mut color = [0.0f64; 3];
+        let mut coverage = 0.0f64;
+        let mut total = 0.0f64;
+        let mut sample = [1.0f32; 4];
+        for &(u, v) in offsets.iter() {
+            let dx = ((u + shift.0).fract() * 2.0 - 1.0) * radius;
+            let dy = ((v + shift.1).fract() * 2.0 - 1.0) * radius;
+            let weight = settings.filter.weight(dx) * settings.filter.weight(dy);
+            let point = view_point(band.image_bounds, (col + dx, row + dy), band.upper_left, band.lower_right, band.rotation);
+            coloring.paint(&fractal.escape(point, scheduler.limit, orbit), &stats, &mut sample[..channels]);
+            let alpha = sample[3] as f64;
+            for c in 0..3 {
+                color[c] += weight * alpha * srgb_to_linear(sample[c]) as f64;
+            }
+            coverage += weight * alpha;
+            total += weight;
+        }
+        let mut pixel = [0.0f32; 4];
+        if coverage != 0.0 {
+            for c in 0..3 {
+                pixel[c] = linear_to_srgb((color[c] / coverage).clamp(0.0, 1.0) as f32);
+            }
+        }
+        pixel[3] = (coverage / total).clamp(0.0, 1.0) as f32;
+        (i, pixel)
+    }).collect();
+
+    for (i, pixel) in resampled {
+        pixels[i * channels..(i + 1) * channels].copy_from_slice(&pixel[..channels]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sample_offsets() {
+        for pattern in [Pattern::Grid, Pattern::RotatedGrid, Pattern::Jittered, Pattern::BlueNoise] {
+            let offsets = sample_offsets(pattern, 9, 1);
+            assert_eq!(offsets.len(), 9);
+            assert!(offsets.iter().all(|&(u, v)| (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)));
+        }
+        assert_eq!(sample_offsets(Pattern::Grid, 5, 0).len(), 9);
+        assert_eq!(sample_offsets(Pattern::Grid, 4, 0), vec![(0.25, 0.25), (0.75, 0.25), (0.25, 0.75), (0.75, 0.75)]);
+    }
+
+    #[test]
+    fn test_filter_weights() {
+        for filter in [Filter::Box, Filter::Tent, Filter::Lanczos] {
+            assert_eq!(filter.weight(0.0), 1.0);
+            assert_eq!(filter.weight(filter.radius()), 0.0);
+        }
+        assert_eq!(Filter::Tent.weight(-0.5), 0.5);
+        assert!(Filter::Lanczos.weight(1.5) < 0.0);
+    }
+
+    #[test]
+    fn test_find_edges() {
+        let pixels = [0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+        assert_eq!(find_edges(&pixels, (3, 1), 3, 0.5), vec![1, 2]);
+        assert_eq!(find_edges(&pixels, (3, 1), 3, 0.0), vec![0, 1, 2]);
+    }
+}
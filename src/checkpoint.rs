@@ -0,0 +1,296 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::time::{Duration, Instant};
+
+use num::Complex;
+
+use crate::accel::Accel;
+use crate::escape::{read_f64, read_u32, read_u64, EscapeField, EscapeSample};
+use crate::fractal::Fractal;
+use crate::scheduler::Tile;
+
+const MAGIC: &[u8; 4] = b"MBCP";
+const VERSION: u32 = 2;
+
+/// How often a checkpoint is forced out to disk when no interval is asked
+/// for.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The file a render of `filename` keeps its checkpoint in.
+pub fn sidecar(filename: &str) -> String {
+    format!("{}.mbcp", filename)
+}
+
+/// Everything that decides what a render's samples are, which a checkpoint
+/// has to agree with before it can be resumed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderParams {
+    pub fractal: Fractal,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub limit: usize,
+    pub tile_size: usize,
+    pub accel: Accel,
+    pub power: f64,
+    pub bailout: f64,
+    pub rotation: f64,
+}
+
+impl RenderParams {
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write_fractal(out, self.fractal)?;
+        for v in [self.upper_left.re, self.upper_left.im, self.lower_right.re, self.lower_right.im] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        for v in [self.bounds.0, self.bounds.1, self.limit, self.tile_size] {
+            out.write_all(&(v as u64).to_le_bytes())?;
+        }
+        let accel: u32 = match self.accel {
+            Accel::None => 0,
+            Accel::MarianiSilver => 1,
+            Accel::BoundaryTrace => 2,
+        };
+        out.write_all(&accel.to_le_bytes())?;
+        for v in [self.power, self.bailout, self.rotation] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn read_from<R: Read>(input: &mut R) -> io::Result<RenderParams> {
+        let fractal = read_fractal(input)?;
+        let upper_left = Complex { re: read_f64(input)?, im: read_f64(input)? };
+        let lower_right = Complex { re: read_f64(input)?, im: read_f64(input)? };
+        let bounds = (read_u64(input)? as usize, read_u64(input)? as usize);
+        let limit = read_u64(input)? as usize;
+        let tile_size = read_u64(input)? as usize;
+        let accel = match read_u32(input)? {
+            0 => Accel::None,
+            1 => Accel::MarianiSilver,
+            2 => Accel::BoundaryTrace,
+            n => return Err(invalid(format!("unknown acceleration {} in checkpoint", n))),
+        };
+        let (power, bailout, rotation) = (read_f64(input)?, read_f64(input)?, read_f64(input)?);
+        Ok(RenderParams { fractal, bounds, upper_left, lower_right, limit, tile_size, accel, power, bailout, rotation })
+    }
+
+    /// Explain the first way in which `self`, read from a checkpoint, differs
+    /// from `wanted`.
+    fn mismatch(&self, wanted: &RenderParams) -> Option<String> {
+        if self.fractal != wanted.fractal {
+            Some(format!("a different fractal ({:?})", self.fractal))
+        } else if self.bounds != wanted.bounds {
+            Some(format!("a different image size ({}x{})", self.bounds.0, self.bounds.1))
+        } else if self.upper_left != wanted.upper_left || self.lower_right != wanted.lower_right {
+            Some(format!("a different region ({} to {})", self.upper_left, self.lower_right))
+        } else if self.limit != wanted.limit {
+            Some(format!("a different iteration limit ({})", self.limit))
+        } else if self.tile_size != wanted.tile_size {
+            Some(format!("a different tile size ({})", self.tile_size))
+        } else if self.accel != wanted.accel {
+            Some(format!("a different acceleration ({:?})", self.accel))
+        } else if self.power != wanted.power {
+            Some(format!("a different power ({})", self.power))
+        } else if self.bailout != wanted.bailout {
+            Some(format!("a different bailout ({})", self.bailout))
+        } else if self.rotation != wanted.rotation {
+            Some(format!("a different rotation ({} degrees)", self.rotation))
+        } else {
+            None
+        }
+    }
+}
+
+/// A sidecar file that finished tiles are appended to as a render goes, so
+/// that it can be picked up where it stopped.
+///
+/// The file is a header holding the `RenderParams`, then one record per
+/// tile: its position and size as four little-endian `u64`s, then its
+/// samples. A record cut short by a crash is dropped on resuming.
+pub struct Checkpoint {
+    out: BufWriter<File>,
+    interval: Duration,
+    last_sync: Instant,
+}
+
+impl Checkpoint {
+    /// Start a new checkpoint at `path`, replacing any that is there.
+    pub fn create(path: &str, params: &RenderParams, interval: Duration) -> io::Result<Checkpoint> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        params.write_to(&mut out)?;
+        out.flush()?;
+        Ok(Checkpoint { out, interval, last_sync: Instant::now() })
+    }
+
+    /// Reopen the checkpoint at `path`, which must have been made with
+    /// `params`, copying the tiles it holds into `field`. Returns the
+    /// checkpoint, ready for more tiles, and the tiles that are done.
+    pub fn resume(path: &str, params: &RenderParams, field: &mut EscapeField, interval: Duration) -> io::Result<(Checkpoint, Vec<Tile>)> {
+        let file = OpenOptions::new().read(true).write(true).open(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("can't resume from {}: {}", path, e)))?;
+        let mut input = BufReader::new(file);
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid(format!("{} is not a checkpoint file", path)));
+        }
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported checkpoint version {}", version)));
+        }
+        let saved = RenderParams::read_from(&mut input)?;
+        if let Some(difference) = saved.mismatch(params) {
+            return Err(invalid(format!("{} was made for {}, so it can't be resumed", path, difference)));
+        }
+
+        let mut good = input.stream_position()?;
+        let mut done = Vec::new();
+        while let Some((tile, samples)) = read_tile(&mut input, field.bounds)? {
+            tile.copy_into(&samples, field);
+            done.push(tile);
+            good = input.stream_position()?;
+        }
+
+        // drop whatever was half written when the last run stopped
+        let mut file = input.into_inner();
+        file.set_len(good)?;
+        file.seek(SeekFrom::Start(good))?;
+        let checkpoint = Checkpoint { out: BufWriter::new(file), interval, last_sync: Instant::now() };
+        Ok((checkpoint, done))
+    }
+
+    /// Add a finished tile. Every `interval` the file is flushed all the way
+    /// to the disk.
+    pub fn record(&mut self, tile: &Tile, samples: &[EscapeSample]) -> io::Result<()> {
+        for v in [tile.x, tile.y, tile.width, tile.height] {
+            self.out.write_all(&(v as u64).to_le_bytes())?;
+        }
+        for s in samples {
+            s.write_to(&mut self.out)?;
+        }
+        if self.last_sync.elapsed() >= self.interval {
+            self.out.flush()?;
+            self.out.get_ref().sync_data()?;
+            self.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// Read the next whole tile record, or `None` at the end of the file or of
+/// its last complete record.
+fn read_tile<R: Read>(input: &mut R, bounds: (usize, usize)) -> io::Result<Option<(Tile, Vec<EscapeSample>)>> {
+    let mut header = [0u8; 32];
+    let mut read = 0;
+    while read < header.len() {
+        match input.read(&mut header[read..])? {
+            0 => return Ok(None),
+            n => read += n,
+        }
+    }
+    let field = |i: usize| u64::from_le_bytes(header[i * 8..i * 8 + 8].try_into().unwrap()) as usize;
+    let tile = Tile { x: field(0), y: field(1), width: field(2), height: field(3) };
+    let fits = |start: usize, len: usize, end: usize| start.checked_add(len).is_some_and(|last| last <= end);
+    if !fits(tile.x, tile.width, bounds.0) || !fits(tile.y, tile.height, bounds.1) {
+        return Err(invalid(format!("checkpoint tile {:?} is outside the image", tile)));
+    }
+
+    let mut samples = Vec::with_capacity(tile.len());
+    for _ in 0..tile.len() {
+        match EscapeSample::read_from(input) {
+            Ok(sample) => samples.push(sample),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some((tile, samples)))
+}
+
+/// Write `fractal` as a little-endian `u32` saying which it is, then the
+/// Julia seed, which is zero for the others.
+pub(crate) fn write_fractal<W: Write>(out: &mut W, fractal: Fractal) -> io::Result<()> {
+    let (kind, seed) = match fractal {
+        Fractal::Mandelbrot => (0u32, Complex { re: 0.0, im: 0.0 }),
+        Fractal::BurningShip => (1, Complex { re: 0.0, im: 0.0 }),
+        Fractal::Julia(seed) => (2, seed),
+    };
+    out.write_all(&kind.to_le_bytes())?;
+    for v in [seed.re, seed.im] {
+        out.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_fractal<R: Read>(input: &mut R) -> io::Result<Fractal> {
+    let kind = read_u32(input)?;
+    let seed = Complex { re: read_f64(input)?, im: read_f64(input)? };
+    match kind {
+        0 => Ok(Fractal::Mandelbrot),
+        1 => Ok(Fractal::BurningShip),
+        2 => Ok(Fractal::Julia(seed)),
+        _ => Err(invalid(format!("unknown fractal {}", kind))),
+    }
+}
+
+pub(crate) fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fractal::{Orbit, BAILOUT, LIMIT, POWER};
+    use crate::scheduler::Scheduler;
+
+    #[test]
+    fn test_resume_renders_the_rest() {
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let scheduler = Scheduler { tile_size: 8, ..Default::default() };
+        let params = RenderParams { fractal: Fractal::Mandelbrot, bounds: (30, 20), upper_left, lower_right, limit: LIMIT, tile_size: 8, accel: scheduler.accel,
+                                   power: POWER, bailout: BAILOUT, rotation: 0.0 };
+        let path = std::env::temp_dir().join("mandelbrot-test-resume.mbcp");
+        let path = path.to_str().unwrap();
+
+        let mut expected = EscapeField::new(params.bounds, upper_left, lower_right);
+        scheduler.render(&mut expected, params.fractal, Orbit::default(), 0.0, |_, _| ());
+
+        // a run that stops after five tiles, partway through writing a sixth
+        let tiles = scheduler.tiles(params.bounds);
+        let mut checkpoint = Checkpoint::create(path, &params, Duration::ZERO).unwrap();
+        let mut first = EscapeField::new(params.bounds, upper_left, lower_right);
+        scheduler.render_tiles(&mut first, params.fractal, Orbit::default(), 0.0, &tiles[..5], |tile, samples| checkpoint.record(tile, samples).unwrap());
+        checkpoint.out.write_all(&[1, 2, 3]).unwrap();
+        drop(checkpoint);
+
+        let mut field = EscapeField::new(params.bounds, upper_left, lower_right);
+        let (mut checkpoint, done) = Checkpoint::resume(path, &params, &mut field, Duration::ZERO).unwrap();
+        assert_eq!(done.len(), 5);
+        let rest: Vec<Tile> = tiles.into_iter().filter(|tile| !done.contains(tile)).collect();
+        scheduler.render_tiles(&mut field, params.fractal, Orbit::default(), 0.0, &rest, |tile, samples| checkpoint.record(tile, samples).unwrap());
+        drop(checkpoint);
+        assert_eq!(field, expected);
+
+        let moved = RenderParams { bounds: (31, 20), ..params };
+        let mut field = EscapeField::new(moved.bounds, upper_left, lower_right);
+        assert!(Checkpoint::resume(path, &moved, &mut field, Duration::ZERO).is_err());
+        let turned = RenderParams { rotation: 45.0, ..params };
+        let mut field = EscapeField::new(turned.bounds, upper_left, lower_right);
+        assert!(Checkpoint::resume(path, &turned, &mut field, Duration::ZERO).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_tile_outside_image() {
+        for (x, width) in [(usize::MAX, 2), (28, 8)] {
+            let mut record = Vec::new();
+            for v in [x, 0, width, 8] {
+                record.extend_from_slice(&(v as u64).to_le_bytes());
+            }
+            let error = read_tile(&mut record.as_slice(), (30, 20)).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+}
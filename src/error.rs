@@ -0,0 +1,120 @@
+use std::fmt;
+use std::io;
+
+use image::ImageError;
+use num::Complex;
+
+use crate::color::COLORLIST;
+
+/// Everything that can stop a render, from a mistyped argument to a full
+/// disk.
+#[derive(Debug)]
+pub enum Error {
+    /// An image size that isn't `<width>x<height>` with both above zero.
+    BadDimensions(String),
+    /// A point that isn't `<re>,<im>`.
+    BadComplex(String),
+    /// A region of the plane with no area, or with its corners the wrong way
+    /// round.
+    EmptyViewport(Complex<f64>, Complex<f64>),
+    UnknownPalette(String),
+    /// Palette knots that no spline can go through, and why.
+    InvalidKnots(String),
+    /// Any other option whose value doesn't parse.
+    BadOption { option: &'static str, value: String, reason: String },
+    Io(io::Error),
+    Image(ImageError),
+    /// A scene file that can't be read as one, and why.
+    BadScene(String),
+    /// A keyframe file that can't be read as one, and why.
+    BadKeyframes(String),
+    /// Something that went wrong with the file at `path`.
+    File { path: String, error: Box<Error> },
+}
+
+/// `Result` with this crate's `Error`.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Blame this error on the file at `path`.
+    pub fn in_file(self, path: &str) -> Error {
+        Error::File { path: path.to_string(), error: Box::new(self) }
+    }
+
+    /// The status the command line exits with on this error. 2 is left to
+    /// usage errors, as clap uses it, and 130 to being interrupted.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::BadOption { .. } => 2,
+            Error::BadDimensions(_) => 3,
+            Error::BadComplex(_) => 4,
+            Error::EmptyViewport(..) => 5,
+            Error::UnknownPalette(_) => 6,
+            Error::InvalidKnots(_) => 7,
+            Error::Io(_) | Error::Image(ImageError::IoError(_)) => 8,
+            Error::Image(_) => 9,
+            Error::BadScene(_) => 10,
+            Error::BadKeyframes(_) => 11,
+            Error::File { error, .. } => error.exit_code(),
+        }
+    }
+}
+
+pub const EXITCODES: &str = "0   success\n2   bad option\n3   bad image size\n4   bad point\n5   empty region\n6   unknown palette\n\
+                             7   invalid palette knots\n8   file error\n9   image encoding error\n10  invalid scene\n\
+                             11  invalid keyframes\n130 interrupted\n";
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BadDimensions(s) => write!(f, "can't use {:?} as an image size, it should be <width>x<height> with both above zero, like 1920x1080", s),
+            Error::BadComplex(s) => write!(f, "can't use {:?} as a point, it should be <re>,<im>, like -0.75,0.1 (put -- before \
+                                               the arguments if any start with a minus sign)", s),
+            Error::EmptyViewport(upper_left, lower_right) => write!(f, "the region from {} to {} is empty, the upper left corner has to be \
+                                                                       above and to the left of the lower right one", upper_left, lower_right),
+            Error::UnknownPalette(name) => write!(f, "unknown palette {:?}, choose from:\n{}", name, COLORLIST),
+            Error::InvalidKnots(reason) => write!(f, "invalid palette knots: {}", reason),
+            Error::BadOption { option, value, reason } => write!(f, "invalid {} {:?}: {}", option, value, reason),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Image(e) => write!(f, "{}", e),
+            Error::BadScene(reason) => write!(f, "invalid scene: {}", reason),
+            Error::BadKeyframes(reason) => write!(f, "invalid keyframes: {}", reason),
+            Error::File { path, error } => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Image(e) => Some(e),
+            Error::File { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<ImageError> for Error {
+    fn from(e: ImageError) -> Error {
+        Error::Image(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_file_errors_keep_their_code() {
+        let error = Error::Io(io::Error::new(io::ErrorKind::NotFound, "not found")).in_file("mandel.mbef");
+        assert_eq!(error.exit_code(), 8);
+        assert_eq!(error.to_string(), "mandel.mbef: not found");
+    }
+}
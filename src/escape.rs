@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use num::Complex;
+
+/// Everything the coloring step needs to know about a single point, so that
+/// a palette can be swapped without iterating the fractal again.
+///
+/// `count` is the integer escape count, `smooth` the continuous (normalized)
+/// iteration count, `z` the orbit value at the moment it escaped, and
+/// `distance` the exterior distance estimate in complex-plane units. Points
+/// that never escaped have `interior` set and zeroes everywhere else.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EscapeSample {
+    pub count: u32,
+    pub smooth: f64,
+    pub z: Complex<f64>,
+    pub distance: f64,
+    pub interior: bool,
+}
+
+impl EscapeSample {
+    pub const INTERIOR: EscapeSample = EscapeSample {
+        count: 0,
+        smooth: 0.0,
+        z: Complex { re: 0.0, im: 0.0 },
+        distance: 0.0,
+        interior: true,
+    };
+
+    /// Build the sample for an orbit that escaped after `count` iterations,
+    /// with final value `z` and derivative `dz`, raised to `power` at every
+    /// step.
+    pub fn escaped(count: usize, z: Complex<f64>, dz: Complex<f64>, power: f64) -> EscapeSample {
+        let modulus = z.norm();
+        let smooth = count as f64 + 1.0 - modulus.ln().log2() / power.log2();
+        // A zero derivative, as at the center of a Julia set, gives no
+        // estimate at all; call it 0 rather than let infinity reach the
+        // coloring.
+        let distance = 0.5 * modulus * modulus.ln() / dz.norm();
+        let distance = if distance.is_finite() { distance } else { 0.0 };
+        EscapeSample { count: count as u32, smooth, z, distance, interior: false }
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&self.count.to_le_bytes())?;
+        out.write_all(&[self.interior as u8])?;
+        for v in [self.smooth, self.z.re, self.z.im, self.distance] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<EscapeSample> {
+        let count = read_u32(input)?;
+        let mut interior = [0u8; 1];
+        input.read_exact(&mut interior)?;
+        let smooth = read_f64(input)?;
+        let z = Complex { re: read_f64(input)?, im: read_f64(input)? };
+        let distance = read_f64(input)?;
+        Ok(EscapeSample { count, smooth, z, distance, interior: interior[0] != 0 })
+    }
+}
+
+impl Default for EscapeSample {
+    fn default() -> Self {
+        EscapeSample::INTERIOR
+    }
+}
+
+const MAGIC: &[u8; 4] = b"MBEF";
+const VERSION: u32 = 1;
+/// The size of one `EscapeSample` record as written by `write_to`.
+const SAMPLE_BYTES: usize = 4 + 1 + 4 * 8;
+
+/// A rendered grid of `EscapeSample`s along with the region of the complex
+/// plane it covers.
+///
+/// Fields can be saved with `write` and loaded again with `read`; the file
+/// layout is a small header followed by one fixed-size little-endian record
+/// per pixel, in row-major order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EscapeField {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    pub samples: Vec<EscapeSample>,
+}
+
+impl EscapeField {
+    pub fn new(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> EscapeField {
+        EscapeField {
+            bounds,
+            upper_left,
+            lower_right,
+            samples: vec![EscapeSample::INTERIOR; bounds.0 * bounds.1],
+        }
+    }
+
+    /// Width of a single pixel on the complex plane.
+    pub fn pixel_size(&self) -> f64 {
+        (self.lower_right.re - self.upper_left.re).abs() / self.bounds.0 as f64
+    }
+
+    pub fn write(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn read(filename: &str) -> io::Result<EscapeField> {
+        EscapeField::read_from(&mut BufReader::new(File::open(filename)?))
+    }
+
+    /// Write the field as `write` does, to any writer.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&(self.bounds.0 as u64).to_le_bytes())?;
+        out.write_all(&(self.bounds.1 as u64).to_le_bytes())?;
+        for v in [self.upper_left.re, self.upper_left.im, self.lower_right.re, self.lower_right.im] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        for s in self.samples.iter() {
+            s.write_to(out)?;
+        }
+        Ok(())
+    }
+
+    /// Read a field written by `write_to`.
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<EscapeField> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an escape field file"));
+        }
+        let version = read_u32(input)?;
+        if version != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("unsupported escape field version {}", version)));
+        }
+        let bounds = (read_u64(input)? as usize, read_u64(input)? as usize);
+        let upper_left = Complex { re: read_f64(input)?, im: read_f64(input)? };
+        let lower_right = Complex { re: read_f64(input)?, im: read_f64(input)? };
+
+        // The header can't be trusted to size anything up front: read only as
+        // many bytes as the input really holds, and then check there were
+        // enough of them.
+        let len = bounds.0.checked_mul(bounds.1)
+            .and_then(|count| (count as u64).checked_mul(SAMPLE_BYTES as u64))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+                                          format!("escape field size {}x{} is too large", bounds.0, bounds.1)))?;
+        let mut bytes = Vec::new();
+        input.take(len).read_to_end(&mut bytes)?;
+        if (bytes.len() as u64) < len {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                      format!("escape field of size {}x{} is cut short", bounds.0, bounds.1)));
+        }
+        let samples = bytes.chunks(SAMPLE_BYTES)
+            .map(|mut record| EscapeSample::read_from(&mut record))
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(EscapeField { bounds, upper_left, lower_right, samples })
+    }
+}
+
+pub fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+pub fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+pub fn read_f64<R: Read>(input: &mut R) -> io::Result<f64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_field_round_trip() {
+        let mut field = EscapeField::new((3, 2), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        field.samples[1] = EscapeSample::escaped(7, Complex { re: 2.5, im: -0.5 }, Complex { re: 30.0, im: 4.0 }, 2.0);
+        let path = std::env::temp_dir().join("mandelbrot-test-field.mbef");
+        let path = path.to_str().unwrap();
+        field.write(path).unwrap();
+        let read = EscapeField::read(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(read, field);
+    }
+
+    #[test]
+    fn test_zero_derivative_distance() {
+        let sample = EscapeSample::escaped(1, Complex { re: 3.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, 2.0);
+        assert_eq!(sample.distance, 0.0);
+    }
+
+    #[test]
+    fn test_bad_field_size() {
+        let field = EscapeField::new((3, 2), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let mut bytes = Vec::new();
+        field.write_to(&mut bytes).unwrap();
+        for bounds in [(u64::MAX, 2u64), (1 << 40, 1 << 40), (3, 3)] {
+            let mut corrupt = bytes.clone();
+            corrupt[8..16].copy_from_slice(&bounds.0.to_le_bytes());
+            corrupt[16..24].copy_from_slice(&bounds.1.to_le_bytes());
+            let error = EscapeField::read_from(&mut corrupt.as_slice()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        let truncated = &bytes[..bytes.len() - 1];
+        assert_eq!(EscapeField::read_from(&mut &truncated[..]).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
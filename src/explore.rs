@@ -0,0 +1,532 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEventKind};
+use crossterm::style::{self, Color, Stylize};
+use crossterm::{cursor, execute, queue, terminal};
+use num::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::color::COLORLIST;
+use crate::error::{Error, Result};
+use crate::fractal::{fit_viewport, view_point, Fractal};
+use crate::output::{to_image, Depth};
+use crate::preview::block_lines;
+use crate::request::RenderRequest;
+use crate::scene::{FractalSpec, Scene, View};
+
+/// How far the arrow keys move the view, as a part of its width or height.
+const PAN_STEP: f64 = 0.1;
+
+/// How many times narrower or wider the zoom keys make the view.
+const ZOOM_STEP: f64 = 2.0;
+
+/// How many degrees the rotate keys turn the view.
+const ROTATE_STEP: f64 = 15.0;
+
+/// How many times more or fewer iterations the iteration keys allow.
+const ITERATION_STEP: f64 = 1.5;
+
+/// Fewer iterations than this show next to nothing.
+const MIN_ITERATIONS: usize = 8;
+
+/// The width of the view when switching to a Julia set, or back from one
+/// that wasn't switched to.
+const JULIA_WIDTH: f64 = 4.0;
+const MANDELBROT_WIDTH: f64 = 4.0;
+const MANDELBROT_CENTER: Complex<f64> = Complex { re: -0.5, im: 0.0 };
+
+pub const EXPLORE_KEYS: &str = "arrows      pan\n\
+                                + -         zoom in and out\n\
+                                < >         turn the view counterclockwise or clockwise\n\
+                                w a s d     move the cursor, which a click does too\n\
+                                c           center the view on the cursor\n\
+                                ] [         more or fewer iterations\n\
+                                p P         next or previous palette\n\
+                                j           the Julia set for the cursor, or back to the Mandelbrot set\n\
+                                b           bookmark the view\n\
+                                1-9         go to a bookmark\n\
+                                r Enter     render the view at full size\n\
+                                ?           show or hide these keys\n\
+                                q Esc       quit\n";
+
+/// What the loop running an `Explorer` has to do after a key.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Redraw,
+    /// Render the scene at full size.
+    Render,
+    /// Save the bookmarks.
+    Bookmark,
+    Quit,
+}
+
+/// Bookmarked views, saved as a TOML file of scenes, each one under
+/// `[[bookmark]]`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmarks {
+    #[serde(default, rename = "bookmark")]
+    pub scenes: Vec<Scene>,
+}
+
+impl Bookmarks {
+    /// Read the bookmarks at `path`, or none if there is no such file yet.
+    pub fn read(path: &str) -> Result<Bookmarks> {
+        if !Path::new(path).exists() {
+            return Ok(Bookmarks::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| Error::from(e).in_file(path))?;
+        toml::from_str(&text).map_err(|e| Error::BadScene(e.to_string()).in_file(path))
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let text = toml::to_string(self).expect("scenes are always valid toml");
+        fs::write(path, text).map_err(|e| Error::from(e).in_file(path))
+    }
+}
+
+/// An interactive look around a fractal in the terminal.
+///
+/// `scene` is what rendering at full size makes, and so it has the size of
+/// the full image. The terminal shows as much of the plane as it can
+/// around the scene's region, which may be a little more than the image
+/// will, since the two are seldom the same shape.
+pub struct Explorer {
+    pub scene: Scene,
+    /// The size of the picture in terminal cells, not counting the status
+    /// line under it.
+    pub screen: (usize, usize),
+    /// The cell the cursor is on.
+    pub cursor: (usize, usize),
+    pub bookmarks: Bookmarks,
+    /// A message for the status line.
+    pub status: String,
+    pub show_keys: bool,
+    /// The Mandelbrot set scene to go back to from a Julia set.
+    parent: Option<(FractalSpec, View)>,
+}
+
+impl Explorer {
+    /// Explore from `scene`, on a screen `screen` cells in size.
+    pub fn new(scene: Scene, screen: (usize, usize), bookmarks: Bookmarks) -> Explorer {
+        let mut explorer = Explorer {
+            scene,
+            screen: (1, 1),
+            cursor: (0, 0),
+            bookmarks,
+            status: String::new(),
+            show_keys: false,
+            parent: None,
+        };
+        explorer.resize(screen);
+        explorer.cursor = (explorer.screen.0 / 2, explorer.screen.1 / 2);
+        explorer
+    }
+
+    /// Fit the picture to a screen `screen` cells in size.
+    pub fn resize(&mut self, screen: (usize, usize)) {
+        self.screen = (screen.0.max(1), screen.1.max(1));
+        self.cursor = (self.cursor.0.min(self.screen.0 - 1), self.cursor.1.min(self.screen.1 - 1));
+    }
+
+    /// The center and width of the scene's region.
+    fn view(&self) -> (Complex<f64>, f64) {
+        let view = &self.scene.view;
+        let center = Complex { re: view.upper_left[0] + view.lower_right[0], im: view.upper_left[1] + view.lower_right[1] } / 2.0;
+        (center, view.lower_right[0] - view.upper_left[0])
+    }
+
+    /// Show a region `width` across centered on `center`, as tall as the
+    /// image's shape calls for, turned as it is now.
+    fn set_view(&mut self, center: Complex<f64>, width: f64) {
+        let size = (self.scene.view.size[0], self.scene.view.size[1]);
+        let height = width * size.1 as f64 / size.0 as f64;
+        let half = Complex { re: width / 2.0, im: -height / 2.0 };
+        self.scene.view = View { rotation: self.scene.view.rotation, ..View::new(size, center - half, center + half) };
+    }
+
+    /// Move the view by `offset`, measured across and up the screen, which
+    /// is turned with the view.
+    fn pan(&mut self, offset: Complex<f64>) {
+        let (center, width) = self.view();
+        let turn = Complex::from_polar(1.0, self.scene.view.rotation.to_radians());
+        self.set_view(center + offset * turn, width);
+    }
+
+    /// Turn the view `degrees` counterclockwise about its center.
+    fn turn(&mut self, degrees: f64) {
+        self.scene.view.rotation = (self.scene.view.rotation + degrees).rem_euclid(360.0);
+    }
+
+    /// The request for the picture in the terminal, two pixels to a cell.
+    pub fn preview_request(&self) -> Result<RenderRequest> {
+        let mut request = self.scene.to_request()?;
+        let bounds = (self.screen.0, 2 * self.screen.1);
+        let (upper_left, lower_right) = fit_viewport(bounds, request.upper_left, request.lower_right);
+        request.antialias = None;
+        Ok(request.size(bounds.0, bounds.1).viewport(upper_left, lower_right))
+    }
+
+    /// The point of the plane in the middle of the cursor's cell.
+    pub fn cursor_point(&self) -> Result<Complex<f64>> {
+        let request = self.preview_request()?;
+        let pixel = (self.cursor.0 as f64 + 0.5, 2.0 * self.cursor.1 as f64 + 1.0);
+        Ok(view_point(request.bounds, pixel, request.upper_left, request.lower_right, request.rotation))
+    }
+
+    /// Put the cursor on the cell at `column` and `row`, if it is in the
+    /// picture.
+    pub fn click(&mut self, column: usize, row: usize) {
+        if column < self.screen.0 && row < self.screen.1 {
+            self.cursor = (column, row);
+        }
+    }
+
+    /// Do what `key` asks.
+    pub fn handle(&mut self, key: KeyEvent) -> Result<Action> {
+        let (center, width) = self.view();
+        let height = width * self.scene.view.size[1] as f64 / self.scene.view.size[0] as f64;
+        self.status.clear();
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(Action::Quit),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(Action::Quit),
+            KeyCode::Left => self.pan(Complex { re: -width * PAN_STEP, im: 0.0 }),
+            KeyCode::Right => self.pan(Complex { re: width * PAN_STEP, im: 0.0 }),
+            KeyCode::Up => self.pan(Complex { re: 0.0, im: height * PAN_STEP }),
+            KeyCode::Down => self.pan(Complex { re: 0.0, im: -height * PAN_STEP }),
+            KeyCode::Char('+') | KeyCode::Char('=') => self.set_view(center, width / ZOOM_STEP),
+            KeyCode::Char('-') | KeyCode::Char('_') => self.set_view(center, width * ZOOM_STEP),
+            KeyCode::Char('<') | KeyCode::Char(',') => self.turn(ROTATE_STEP),
+            KeyCode::Char('>') | KeyCode::Char('.') => self.turn(-ROTATE_STEP),
+            KeyCode::Char('w') => self.cursor.1 = self.cursor.1.saturating_sub(1),
+            KeyCode::Char('s') => self.cursor.1 = (self.cursor.1 + 1).min(self.screen.1 - 1),
+            KeyCode::Char('a') => self.cursor.0 = self.cursor.0.saturating_sub(1),
+            KeyCode::Char('d') => self.cursor.0 = (self.cursor.0 + 1).min(self.screen.0 - 1),
+            KeyCode::Char('c') => {
+                self.set_view(self.cursor_point()?, width);
+                self.cursor = (self.screen.0 / 2, self.screen.1 / 2);
+            }
+            KeyCode::Char(']') => {
+                let limit = &mut self.scene.render.limit;
+                *limit = ((*limit as f64 * ITERATION_STEP).round() as usize).max(*limit + 1);
+            }
+            KeyCode::Char('[') => {
+                let limit = &mut self.scene.render.limit;
+                *limit = ((*limit as f64 / ITERATION_STEP).round() as usize).max(MIN_ITERATIONS);
+            }
+            KeyCode::Char('p') => self.next_palette(1),
+            KeyCode::Char('P') => self.next_palette(COLORLIST.names().count() - 1),
+            KeyCode::Char('j') => self.toggle_julia()?,
+            KeyCode::Char('b') => {
+                self.bookmarks.scenes.push(self.scene.clone());
+                self.status = format!("bookmark {}", self.bookmarks.scenes.len());
+                return Ok(Action::Bookmark);
+            }
+            KeyCode::Char(digit @ '1'..='9') => {
+                let n = digit as usize - '0' as usize;
+                match self.bookmarks.scenes.get(n - 1) {
+                    Some(bookmark) => {
+                        // the size and file stay as they were asked for
+                        let (size, output) = (self.scene.view.size, self.scene.output.clone());
+                        self.scene = Scene { output, ..bookmark.clone() };
+                        let (center, width) = self.view();
+                        self.scene.view.size = size;
+                        self.set_view(center, width);
+                        self.parent = None;
+                    }
+                    None => self.status = format!("there is no bookmark {}", n),
+                }
+            }
+            KeyCode::Char('r') | KeyCode::Enter => return Ok(Action::Render),
+            KeyCode::Char('?') => self.show_keys = !self.show_keys,
+            _ => (),
+        }
+        Ok(Action::Redraw)
+    }
+
+    /// Move `steps` palettes on through the built in ones.
+    fn next_palette(&mut self, steps: usize) {
+        let names: Vec<&str> = COLORLIST.names().collect();
+        let current = names.iter().position(|name| name.eq_ignore_ascii_case(&self.scene.color.palette)).unwrap_or(0);
+        self.scene.color.palette = names[(current + steps) % names.len()].to_string();
+    }
+
+    /// Switch to the Julia set for the point under the cursor, or from a
+    /// Julia set back to where the Mandelbrot set was.
+    fn toggle_julia(&mut self) -> Result<()> {
+        match self.scene.fractal.fractal()? {
+            Fractal::Mandelbrot => {
+                let seed = self.cursor_point()?;
+                self.parent = Some((self.scene.fractal, self.scene.view));
+                self.scene.fractal = FractalSpec::new(Fractal::Julia(seed), self.scene.fractal.orbit());
+                self.set_view(Complex { re: 0.0, im: 0.0 }, JULIA_WIDTH);
+                self.status = format!("julia set for {}", seed);
+            }
+            Fractal::Julia(_) => match self.parent.take() {
+                Some((fractal, view)) => (self.scene.fractal, self.scene.view) = (fractal, view),
+                None => {
+                    self.scene.fractal = FractalSpec::new(Fractal::Mandelbrot, self.scene.fractal.orbit());
+                    self.set_view(MANDELBROT_CENTER, MANDELBROT_WIDTH);
+                }
+            },
+            Fractal::BurningShip => self.status = "the burning ship has no julia sets here".to_string(),
+        }
+        Ok(())
+    }
+
+    /// The status line: where the view is and what it is rendered with.
+    fn status_line(&self) -> String {
+        let (center, width) = self.view();
+        let fractal = match self.scene.fractal.fractal() {
+            Ok(Fractal::Julia(seed)) => format!("julia {}", seed),
+            Ok(Fractal::BurningShip) => "burning ship".to_string(),
+            _ => "mandelbrot".to_string(),
+        };
+        let rotation = match self.scene.view.rotation {
+            0.0 => String::new(),
+            degrees => format!(" turned {}\u{b0}", degrees),
+        };
+        let line = format!("{} | center {} width {:.3e}{} | {} iterations | {} | {}",
+                           fractal, center, width, rotation, self.scene.render.limit, self.scene.color.palette,
+                           if self.status.is_empty() { "? for keys" } else { &self.status });
+        line.chars().take(self.screen.0).collect()
+    }
+
+    /// Draw the picture, the cursor and the status line to `out`.
+    pub fn draw<W: Write>(&self, out: &mut W) -> Result<()> {
+        let request = self.preview_request()?;
+        let pixels = request.colorize(&request.render_escape()?);
+        let image = to_image(&pixels, request.bounds, request.coloring.channels(), Depth::Eight).into_rgba8();
+        for (row, line) in block_lines(&image).iter().enumerate() {
+            queue!(out, cursor::MoveTo(0, row as u16), style::Print(line))?;
+        }
+
+        // a cross in black or white, whichever stands out from the cell
+        let (column, row) = (self.cursor.0 as u32, 2 * self.cursor.1 as u32);
+        let pixel = image.get_pixel(column, row);
+        let luma = 0.2126 * pixel[0] as f64 + 0.7152 * pixel[1] as f64 + 0.0722 * pixel[2] as f64;
+        let mark = if luma > 128.0 { "+".with(Color::Black).on(Color::White) } else { "+".with(Color::White).on(Color::Black) };
+        queue!(out, cursor::MoveTo(column as u16, self.cursor.1 as u16), style::PrintStyledContent(mark))?;
+
+        if self.show_keys {
+            for (row, line) in EXPLORE_KEYS.lines().enumerate().take(self.screen.1) {
+                let line: String = format!(" {:<width$}", line, width = self.screen.0.saturating_sub(1)).chars().take(self.screen.0).collect();
+                queue!(out, cursor::MoveTo(0, row as u16), style::PrintStyledContent(line.with(Color::White).on(Color::Black)))?;
+            }
+        }
+        queue!(out, cursor::MoveTo(0, self.screen.1 as u16), terminal::Clear(terminal::ClearType::CurrentLine),
+               style::Print(self.status_line()))?;
+        Ok(out.flush()?)
+    }
+}
+
+/// Run `explorer` full screen until it is quit, saving bookmarks to
+/// `bookmarks` and calling `render` with the scene whenever it is asked to
+/// render it at full size. `render` gives back a message for the status
+/// line.
+pub fn run<F>(mut explorer: Explorer, bookmarks: &str, mut render: F) -> Result<()>
+    where F: FnMut(&Scene) -> Result<String> {
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen, cursor::Hide, event::EnableMouseCapture)?;
+    let result = explore(&mut explorer, &mut out, bookmarks, &mut render);
+    // put the terminal back whatever happened
+    let restored = execute!(out, event::DisableMouseCapture, cursor::Show, terminal::LeaveAlternateScreen);
+    terminal::disable_raw_mode()?;
+    result.and(Ok(restored?))
+}
+
+fn explore<W, F>(explorer: &mut Explorer, out: &mut W, bookmarks: &str, render: &mut F) -> Result<()>
+    where W: Write, F: FnMut(&Scene) -> Result<String> {
+    let (columns, rows) = terminal::size()?;
+    explorer.resize((columns as usize, rows.saturating_sub(1) as usize));
+    explorer.draw(out)?;
+    loop {
+        let action = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => explorer.handle(key)?,
+            Event::Mouse(mouse) if mouse.kind == MouseEventKind::Down(MouseButton::Left) => {
+                explorer.click(mouse.column as usize, mouse.row as usize);
+                Action::Redraw
+            }
+            Event::Resize(columns, rows) => {
+                explorer.resize((columns as usize, rows.saturating_sub(1) as usize));
+                queue!(out, terminal::Clear(terminal::ClearType::All))?;
+                Action::Redraw
+            }
+            _ => continue,
+        };
+        match action {
+            Action::Quit => return Ok(()),
+            Action::Redraw => (),
+            Action::Bookmark => {
+                if let Err(e) = explorer.bookmarks.write(bookmarks) {
+                    explorer.status = e.to_string();
+                }
+            }
+            Action::Render => {
+                explorer.status = format!("rendering {}x{}...", explorer.scene.view.size[0], explorer.scene.view.size[1]);
+                explorer.draw(out)?;
+                explorer.status = render(&explorer.scene).unwrap_or_else(|e| e.to_string());
+            }
+        }
+        explorer.draw(out)?;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fractal::Orbit;
+    use crate::output::OutputOptions;
+    use crate::scene::OutputSpec;
+
+    fn explorer() -> Explorer {
+        let request = RenderRequest::mandelbrot().size(800, 600).iterations(50);
+        let scene = Scene {
+            fractal: FractalSpec::new(request.fractal, request.orbit),
+            view: View::new(request.bounds, request.upper_left, request.lower_right),
+            render: request.scheduler,
+            color: Default::default(),
+            antialias: None,
+            output: OutputSpec::new("explore.png", None, &OutputOptions::default()),
+        };
+        Explorer::new(scene, (40, 15), Bookmarks::default())
+    }
+
+    fn press(explorer: &mut Explorer, code: KeyCode) -> Action {
+        explorer.handle(KeyEvent::new(code, KeyModifiers::NONE)).unwrap()
+    }
+
+    fn close(a: Complex<f64>, b: Complex<f64>) -> bool {
+        (a - b).norm() < 1e-9
+    }
+
+    #[test]
+    fn test_explorer_view() {
+        let mut explorer = explorer();
+        assert_eq!(explorer.cursor, (20, 7));
+        assert!(close(explorer.view().0, Complex { re: -0.5, im: 0.0 }));
+        // a 40x30 picture of a 4:3 region shows all of it
+        let request = explorer.preview_request().unwrap();
+        assert_eq!(request.bounds, (40, 30));
+        assert!(close(request.upper_left, Complex { re: -2.5, im: 1.5 }));
+        assert!(close(explorer.cursor_point().unwrap(), Complex { re: -0.45, im: 0.0 }));
+
+        press(&mut explorer, KeyCode::Right);
+        press(&mut explorer, KeyCode::Up);
+        assert!(close(explorer.view().0, Complex { re: -0.1, im: 0.3 }));
+        press(&mut explorer, KeyCode::Char('+'));
+        assert_eq!(explorer.view().1, 2.0);
+        assert_eq!(explorer.scene.view.size, [800, 600]);
+        assert_eq!(explorer.scene.view.upper_left[1] - explorer.scene.view.lower_right[1], 1.5);
+
+        explorer.click(0, 0);
+        press(&mut explorer, KeyCode::Char('c'));
+        assert!(close(explorer.view().0, Complex { re: -1.075, im: 1.0 }));
+        assert_eq!(explorer.cursor, (20, 7));
+
+        for _ in 0..50 {
+            press(&mut explorer, KeyCode::Char('s'));
+        }
+        assert_eq!(explorer.cursor, (20, 14));
+        assert_eq!(press(&mut explorer, KeyCode::Char('r')), Action::Render);
+        assert_eq!(press(&mut explorer, KeyCode::Esc), Action::Quit);
+    }
+
+    #[test]
+    fn test_explorer_rotation() {
+        let mut explorer = explorer();
+        for _ in 0..6 {
+            press(&mut explorer, KeyCode::Char('<'));
+        }
+        assert_eq!(explorer.scene.view.rotation, 90.0);
+        assert_eq!(explorer.preview_request().unwrap().rotation, 90.0);
+        // right on the screen is now up the plane, and the cursor turns too
+        press(&mut explorer, KeyCode::Right);
+        assert!(close(explorer.view().0, Complex { re: -0.5, im: 0.4 }));
+        assert!(close(explorer.cursor_point().unwrap(), Complex { re: -0.5, im: 0.45 }));
+        press(&mut explorer, KeyCode::Char('+'));
+        assert_eq!(explorer.scene.view.rotation, 90.0);
+        explorer.resize((120, 15));
+        assert!(explorer.status_line().contains("turned 90\u{b0}"));
+
+        press(&mut explorer, KeyCode::Char('b'));
+        press(&mut explorer, KeyCode::Char('>'));
+        assert_eq!(explorer.scene.view.rotation, 75.0);
+        press(&mut explorer, KeyCode::Char('1'));
+        assert_eq!(explorer.scene.view.rotation, 90.0);
+        for _ in 0..6 {
+            press(&mut explorer, KeyCode::Char('.'));
+        }
+        assert_eq!(explorer.scene.view.rotation, 0.0);
+    }
+
+    #[test]
+    fn test_explorer_settings() {
+        let mut explorer = explorer();
+        press(&mut explorer, KeyCode::Char(']'));
+        assert_eq!(explorer.scene.render.limit, 75);
+        for _ in 0..10 {
+            press(&mut explorer, KeyCode::Char('['));
+        }
+        assert_eq!(explorer.scene.render.limit, MIN_ITERATIONS);
+
+        press(&mut explorer, KeyCode::Char('p'));
+        assert_eq!(explorer.scene.color.palette, "viridis");
+        press(&mut explorer, KeyCode::Char('P'));
+        press(&mut explorer, KeyCode::Char('P'));
+        assert_eq!(explorer.scene.color.palette, "vaportest");
+        explorer.scene.to_request().unwrap();
+    }
+
+    #[test]
+    fn test_explorer_julia() {
+        let mut explorer = explorer();
+        press(&mut explorer, KeyCode::Char('+'));
+        let before = explorer.scene.clone();
+        let seed = explorer.cursor_point().unwrap();
+        press(&mut explorer, KeyCode::Char('j'));
+        assert_eq!(explorer.scene.fractal.fractal().unwrap(), Fractal::Julia(seed));
+        assert!(close(explorer.view().0, Complex { re: 0.0, im: 0.0 }));
+        press(&mut explorer, KeyCode::Char('j'));
+        assert_eq!(explorer.scene, before);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let mut explorer = explorer();
+        assert_eq!(press(&mut explorer, KeyCode::Char('b')), Action::Bookmark);
+        press(&mut explorer, KeyCode::Char('j'));
+        press(&mut explorer, KeyCode::Char('p'));
+        assert_eq!(press(&mut explorer, KeyCode::Char('b')), Action::Bookmark);
+
+        let text = toml::to_string(&explorer.bookmarks).unwrap();
+        assert!(text.contains("[[bookmark]]"));
+        let bookmarks: Bookmarks = toml::from_str(&text).unwrap();
+        assert_eq!(bookmarks, explorer.bookmarks);
+
+        explorer.scene.view.size = [100, 100];
+        press(&mut explorer, KeyCode::Char('1'));
+        assert_eq!(explorer.scene.fractal, FractalSpec::new(Fractal::Mandelbrot, Orbit::default()));
+        assert_eq!(explorer.scene.view.size, [100, 100]);
+        assert!(close(explorer.view().0, Complex { re: -0.5, im: 0.0 }));
+        press(&mut explorer, KeyCode::Char('2'));
+        assert_eq!(explorer.scene.color.palette, "viridis");
+        press(&mut explorer, KeyCode::Char('3'));
+        assert_eq!(explorer.status, "there is no bookmark 3");
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut explorer = explorer();
+        explorer.show_keys = true;
+        let mut out = Vec::new();
+        explorer.draw(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.matches('\u{2580}').count(), 40 * 15);
+        assert!(text.contains("mandelbrot | center -0.5+0i"));
+        assert!(text.contains(" r Enter     render"));
+    }
+}
@@ -0,0 +1,365 @@
+use std::borrow::Cow;
+use std::f64::consts::TAU;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+
+use num::Complex;
+use rayon::prelude::*;
+
+use crate::animate::Animation;
+use crate::checkpoint::{invalid, read_fractal, write_fractal};
+use crate::error::{Error, Result};
+use crate::escape::{read_f64, read_u32, read_u64, EscapeField, EscapeSample};
+use crate::fractal::{pixel_to_point, Fractal, Orbit};
+use crate::progress;
+use crate::request::RenderRequest;
+
+const MAGIC: &[u8; 4] = b"MBEM";
+const VERSION: u32 = 1;
+
+/// A fractal rendered on an exponential map around `center`: one strip that
+/// goes once round the center from left to right, and in towards it from
+/// top to bottom, by the same factor every row. A strip a few thousand
+/// samples wide holds a zoom through many orders of magnitude, which a
+/// `Reprojection` turns back into ordinary frames.
+///
+/// The field's plane is that of `ln(z - center)` turned on its side: a
+/// sample at `(x, y)` of that plane is the point `center + e^y * e^(ix)`, so
+/// `x` runs from 0 to 2π and `y` down from the log of the outer radius.
+/// Rows are as far apart as columns, which keeps the samples square.
+///
+/// Orbits are followed with the usual power and bailout. Maps can be saved
+/// with `write` and loaded again with `read`; the file is a header giving
+/// the fractal, iteration limit and center, followed by the field as
+/// `EscapeField::write` lays it out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpMap {
+    pub fractal: Fractal,
+    pub limit: usize,
+    pub center: Complex<f64>,
+    pub field: EscapeField,
+}
+
+impl ExpMap {
+    /// A map of `fractal` around `center`, `width` samples round, from
+    /// `radius` in to `radius / depth`, not yet rendered. `width` must be
+    /// above zero, and `radius` and `depth` finite with `depth` above 1.
+    pub fn new(fractal: Fractal, limit: usize, center: Complex<f64>, width: usize, radius: f64, depth: f64) -> ExpMap {
+        let step = TAU / width as f64;
+        let height = (depth.ln() / step).ceil().max(1.0) as usize;
+        let top = radius.ln();
+        let field = EscapeField::new((width, height),
+                                     Complex { re: 0.0, im: top },
+                                     Complex { re: TAU, im: top - height as f64 * step });
+        ExpMap { fractal, limit, center, field }
+    }
+
+    /// The distance from the center of the first row.
+    pub fn radius(&self) -> f64 {
+        self.field.upper_left.im.exp()
+    }
+
+    /// The distance from the center of the last row.
+    pub fn inner_radius(&self) -> f64 {
+        (self.field.upper_left.im - self.step() * (self.field.bounds.1 - 1) as f64).exp()
+    }
+
+    /// The angle between columns, which is also the log of the ratio between
+    /// the radii of one row and the next.
+    fn step(&self) -> f64 {
+        TAU / self.field.bounds.0 as f64
+    }
+
+    /// The point of the fractal's plane that sample `(column, row)` is of.
+    pub fn point(&self, column: usize, row: usize) -> Complex<f64> {
+        let step = self.step();
+        let w = Complex { re: self.field.upper_left.im - row as f64 * step, im: column as f64 * step };
+        self.center + w.exp()
+    }
+
+    /// Iterate every sample, rows in parallel, calling `on_row` with each
+    /// row's samples as it finishes. Rows not yet started when the render is
+    /// cancelled are left interior.
+    pub fn render<F>(&mut self, on_row: F) where F: Fn(&[EscapeSample]) + Sync {
+        let width = self.field.bounds.0;
+        let map = &*self;
+        let rows: Vec<Vec<EscapeSample>> = (0..self.field.bounds.1).into_par_iter()
+            .map(|row| {
+                if progress::cancelled() {
+                    return vec![EscapeSample::INTERIOR; width];
+                }
+                let samples: Vec<_> = (0..width)
+                    .map(|column| map.fractal.escape(map.point(column, row), map.limit, Orbit::default()))
+                    .collect();
+                on_row(&samples);
+                samples
+            })
+            .collect();
+        self.field.samples = rows.concat();
+    }
+
+    /// The sample for the point `z`, blended from the four around it.
+    ///
+    /// The smooth count and distance are interpolated when all four escaped,
+    /// so that they stay continuous; everything else comes from the nearest
+    /// of them. Points outside the map take the sample on its edge.
+    pub fn sample_at(&self, z: Complex<f64>) -> EscapeSample {
+        let (width, height) = self.field.bounds;
+        let step = self.step();
+        let d = z - self.center;
+        let column = d.im.atan2(d.re).rem_euclid(TAU) / step;
+        let row = if d.norm_sqr() > 0.0 {
+            ((self.field.upper_left.im - d.norm().ln()) / step).clamp(0.0, (height - 1) as f64)
+        } else {
+            (height - 1) as f64
+        };
+
+        let (x0, y0) = (column.floor() as usize % width, row.floor() as usize);
+        let (x1, y1) = ((x0 + 1) % width, (y0 + 1).min(height - 1));
+        let (fx, fy) = (column.fract(), row.fract());
+        let at = |x: usize, y: usize| &self.field.samples[y * width + x];
+        let corners = [at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1)];
+        let weights = [(1.0 - fx) * (1.0 - fy), fx * (1.0 - fy), (1.0 - fx) * fy, fx * fy];
+
+        let nearest = corners[(fx >= 0.5) as usize + 2 * (fy >= 0.5) as usize];
+        let mut sample = *nearest;
+        if corners.iter().all(|s| !s.interior) {
+            let blend = |value: fn(&EscapeSample) -> f64| -> f64 {
+                corners.iter().zip(weights).map(|(s, w)| value(s) * w).sum()
+            };
+            sample.smooth = blend(|s| s.smooth);
+            sample.distance = blend(|s| s.distance);
+        }
+        sample
+    }
+
+    /// The field of an ordinary `bounds` sized image of the region from
+    /// `upper_left` to `lower_right`, taken from the map rather than
+    /// rendered.
+    pub fn reproject(&self, bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> EscapeField {
+        let mut field = EscapeField::new(bounds, upper_left, lower_right);
+        field.samples.par_chunks_mut(bounds.0).enumerate().for_each(|(row, samples)| {
+            for (column, sample) in samples.iter_mut().enumerate() {
+                *sample = self.sample_at(pixel_to_point(bounds, (column, row), upper_left, lower_right));
+            }
+        });
+        field
+    }
+
+    /// The field to color the strip itself with, for a look at it. Distances
+    /// are measured in the strip's units, which shrink with the radius, so
+    /// that distance coloring shows edges of the same width all the way
+    /// down.
+    pub fn preview(&self) -> EscapeField {
+        let mut field = self.field.clone();
+        let width = field.bounds.0;
+        for (row, samples) in field.samples.chunks_mut(width).enumerate() {
+            let radius = (self.field.upper_left.im - self.step() * row as f64).exp();
+            for sample in samples {
+                sample.distance /= radius;
+            }
+        }
+        field
+    }
+
+    pub fn write(&self, filename: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(filename)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn read(filename: &str) -> io::Result<ExpMap> {
+        ExpMap::read_from(&mut BufReader::new(File::open(filename)?))
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        write_fractal(out, self.fractal)?;
+        out.write_all(&(self.limit as u64).to_le_bytes())?;
+        for v in [self.center.re, self.center.im] {
+            out.write_all(&v.to_le_bytes())?;
+        }
+        self.field.write_to(out)
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<ExpMap> {
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not an exponential map file".to_string()));
+        }
+        let version = read_u32(input)?;
+        if version != VERSION {
+            return Err(invalid(format!("unsupported exponential map version {}", version)));
+        }
+        let fractal = read_fractal(input)?;
+        let limit = read_u64(input)? as usize;
+        let center = Complex { re: read_f64(input)?, im: read_f64(input)? };
+        let field = EscapeField::read_from(input)?;
+        if field.bounds.0 == 0 || field.bounds.1 == 0 {
+            return Err(invalid("empty exponential map".to_string()));
+        }
+        Ok(ExpMap { fractal, limit, center, field })
+    }
+}
+
+/// The zoom into the center of an `ExpMap`, as ordinary frames taken from
+/// it rather than rendered.
+///
+/// The first frame has its corners on the map's outer edge and the last
+/// its pixels the size of the map's innermost samples, with the zoom going
+/// by the same factor every frame in between.
+pub struct Reprojection {
+    pub map: ExpMap,
+    /// The first frame. Later ones differ only in their region.
+    pub start: RenderRequest,
+    pub frames: usize,
+    /// How much deeper the last frame is than the first.
+    pub depth: f64,
+}
+
+impl Reprojection {
+    /// The zoom as deep as `map` goes, in `frames` frames the size and
+    /// coloring of `request`.
+    pub fn new(map: ExpMap, request: RenderRequest, frames: usize) -> Result<Reprojection> {
+        let (width, height) = request.bounds;
+        if width == 0 || height == 0 {
+            return Err(Error::BadDimensions(format!("{}x{}", width, height)));
+        }
+        // the corners of the last frame are this many of its pixels from
+        // the center
+        let corner = (width as f64).hypot(height as f64) / 2.0;
+        let depth = map.radius() / (map.inner_radius() * corner);
+        if depth <= 1.0 {
+            return Err(Error::BadOption {
+                option: "strip",
+                value: format!("{}x{}", map.field.bounds.0, map.field.bounds.1),
+                reason: format!("it doesn't go deep enough for {}x{} frames, render it with a larger ZOOM", width, height),
+            });
+        }
+
+        let mut start = request;
+        start.fractal = map.fractal;
+        start.scheduler.limit = map.limit;
+        (start.orbit, start.rotation) = (Orbit::default(), 0.0);
+        let mut reprojection = Reprojection { map, start, frames, depth };
+        reprojection.start = reprojection.frame(0);
+        Ok(reprojection)
+    }
+
+    /// How many samples round the map needs for frames this size to have
+    /// no fewer samples than pixels, even at their corners.
+    pub fn sharp_width(&self) -> usize {
+        let (width, height) = self.start.bounds;
+        (std::f64::consts::PI * (width as f64).hypot(height as f64)).ceil() as usize
+    }
+}
+
+impl Animation for Reprojection {
+    fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Times outside the frames are held at the first or last, as the map
+    /// has nothing further out or in.
+    fn at(&self, time: f64) -> RenderRequest {
+        let t = if self.frames > 1 { (time / (self.frames - 1) as f64).clamp(0.0, 1.0) } else { 0.0 };
+        let (width, height) = (self.start.bounds.0 as f64, self.start.bounds.1 as f64);
+        let corner = self.map.radius() * self.depth.powf(-t);
+        let half = Complex { re: width, im: height } * (corner / width.hypot(height));
+        let center = self.map.center;
+        self.start.clone().viewport(center + Complex { re: -half.re, im: half.im },
+                                    center + Complex { re: half.re, im: -half.im })
+    }
+
+    /// The field taken from the map rather than rendered.
+    fn field(&self, request: &RenderRequest) -> Cow<'_, EscapeField> {
+        Cow::Owned(self.map.reproject(request.bounds, request.upper_left, request.lower_right))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn map() -> ExpMap {
+        let mut map = ExpMap::new(Fractal::Mandelbrot, 200, Complex { re: -0.75, im: 0.1 }, 256, 1.0, 1000.0);
+        map.render(|_| ());
+        map
+    }
+
+    #[test]
+    fn test_expmap() {
+        let map = map();
+        assert_eq!(map.field.bounds.0, 256);
+        assert_eq!(map.field.bounds.1, (256.0 * 1000f64.ln() / TAU).ceil() as usize);
+        assert!((map.radius() - 1.0).abs() < 1e-12);
+        assert!(map.inner_radius() <= 1e-3 * (1.0 + TAU / 256.0));
+
+        // samples are of the points they say, and are found again there
+        for &(column, row) in &[(0, 0), (17, 3), (200, 50), (255, map.field.bounds.1 - 1)] {
+            let point = map.point(column, row);
+            let sample = map.field.samples[row * 256 + column];
+            assert_eq!(sample, map.fractal.escape(point, map.limit, Orbit::default()));
+            let found = map.sample_at(point);
+            assert_eq!(found.count, sample.count);
+            assert_eq!(found.interior, sample.interior);
+            if !sample.interior {
+                assert!((found.smooth - sample.smooth).abs() < 1e-6);
+            }
+        }
+        assert!((map.point(64, 0) - Complex { re: -0.75, im: 1.1 }).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_expmap_file() {
+        let mut map = map();
+        map.fractal = Fractal::Julia(Complex { re: -0.8, im: 0.156 });
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        assert_eq!(ExpMap::read_from(&mut &bytes[..]).unwrap(), map);
+
+        bytes[0] = b'X';
+        assert!(ExpMap::read_from(&mut &bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_reprojection() {
+        let mut map = ExpMap::new(Fractal::Mandelbrot, 200, Complex { re: -0.75, im: 0.1 }, 512, 1.0, 100.0);
+        map.render(|_| ());
+        let request = RenderRequest::mandelbrot().size(32, 24);
+        let reprojection = Reprojection::new(map, request, 5).unwrap();
+        // the corners of 32x24 frames are 20 pixels from the center
+        assert_eq!(reprojection.depth, reprojection.map.radius() / (reprojection.map.inner_radius() * 20.0));
+        assert_eq!(reprojection.sharp_width(), 126);
+
+        let first = reprojection.frame(0);
+        let corner = first.upper_left - reprojection.map.center;
+        assert!((corner.norm() - 1.0).abs() < 1e-12);
+        assert!((first.lower_right - reprojection.map.center + corner).norm() < 1e-12);
+        let last = reprojection.frame(4);
+        let ratio = (first.lower_right.re - first.upper_left.re) / (last.lower_right.re - last.upper_left.re);
+        assert!((ratio - reprojection.depth).abs() < 1e-9 * ratio);
+
+        // frames taken from the map agree with the same frames rendered
+        let mut agree = 0;
+        let mut total = 0;
+        reprojection.render(|index, request, field, _| {
+            assert_eq!(request.upper_left, reprojection.frame(index).upper_left);
+            let rendered = request.render_escape().unwrap();
+            for (a, b) in field.samples.iter().zip(&rendered.samples) {
+                total += 1;
+                if a.interior == b.interior && a.count.abs_diff(b.count) <= 1 {
+                    agree += 1;
+                }
+            }
+            Ok(())
+        }).unwrap();
+        assert_eq!(total, 5 * 32 * 24);
+        assert!(agree as f64 > 0.9 * total as f64, "{} of {}", agree, total);
+
+        let shallow = ExpMap::new(Fractal::Mandelbrot, 200, Complex { re: -0.75, im: 0.1 }, 64, 1.0, 10.0);
+        assert!(Reprojection::new(shallow, RenderRequest::mandelbrot().size(32, 24), 5).is_err());
+    }
+}
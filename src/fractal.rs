@@ -1,59 +1,162 @@
-use std::fs::File;
 use std::str::FromStr;
 
-use image::{ColorType, ImageResult};
-use image::png::PngEncoder;
 use num::Complex;
 
-use crate::color::{Color, color};
+use crate::error::Error;
+use crate::escape::EscapeSample;
 
-/// Try to determine if `c` is in the Julia set, using at most `limit`
-/// iterations to decide.
+/// Try to determine if `c` is in the Mandelbrot set, following its orbit
+/// as `orbit` says for at most `limit` iterations.
 ///
-/// If `c` is not a member, return `Some(i)`, where `i` is the number of
-/// iterations it took for `c` to leave the circle of radius 2 centered on the
-/// origin. If `c` seems to be a member (more precisely, if we reached the
-/// iteration limit without being able to prove that `c` is not a member),
-/// return `None`.
-fn escape_time_mandel(c: Complex<f64>, limit: usize) -> Option<usize> {
+/// If `c` is not a member, return the `EscapeSample` describing how its orbit
+/// left the circle of radius `orbit.bailout` centered on the origin. If `c`
+/// seems to be a member (more precisely, if we reached the iteration limit
+/// without being able to prove that `c` is not a member), return
+/// `EscapeSample::INTERIOR`.
+fn escape_time_mandel(c: Complex<f64>, limit: usize, orbit: Orbit) -> EscapeSample {
     let mut z: Complex<f64> = Complex{ re: 0.0, im: 0.0 };
+    let mut dz: Complex<f64> = Complex{ re: 0.0, im: 0.0 };
+    let radius_sqr = orbit.bailout * orbit.bailout;
     for i in 0..limit {
-        if z.norm_sqr() >= 4. {
-            return Some(i)
+        if z.norm_sqr() >= radius_sqr {
+            return EscapeSample::escaped(i, z, dz, orbit.power)
         }
-        z = z * z + c;
+        let (raised, slope) = orbit.raise(z);
+        dz = slope * dz + 1.0;
+        z = raised + c;
     }
-    None
+    EscapeSample::INTERIOR
 }
 
-fn escape_time_burningship(c: Complex<f64>, limit: usize) -> Option<usize> {
+fn escape_time_burningship(c: Complex<f64>, limit: usize, orbit: Orbit) -> EscapeSample {
     let mut z: Complex<f64> = Complex{ re: 0.0, im: 0.0 };
+    let mut dz: Complex<f64> = Complex{ re: 0.0, im: 0.0 };
+    let radius_sqr = orbit.bailout * orbit.bailout;
     for i in 0..limit {
-        if z.norm_sqr() >= 4. {
-            return Some(i)
+        if z.norm_sqr() >= radius_sqr {
+            return EscapeSample::escaped(i, z, dz, orbit.power)
         }
-        z = Complex { re: z.re.abs(), im: -z.im.abs() }.powi(2) + c;
+        let folded = Complex { re: z.re.abs(), im: -z.im.abs() };
+        // the fold makes z a non-holomorphic function of c, so there is no
+        // complex derivative to track; this leaves the fold's Jacobian out,
+        // which is only an approximation, good enough for distance coloring
+        let (raised, slope) = orbit.raise(folded);
+        dz = slope * dz + 1.0;
+        z = raised + c;
     }
-    None
+    EscapeSample::INTERIOR
 }
 
-/// Try to determine if `c` is in the Julia set, using at most `limit`
-/// iterations to decide.
+/// Try to determine if `z` is in the Julia set for `c`, following its orbit
+/// as `orbit` says for at most `limit` iterations.
 ///
-/// If `c` is not a member, return `Some(i)`, where `i` is the number of
-/// iterations it took for `c` to leave the circle of radius 2 centered on the
-/// origin. If `c` seems to be a member (more precisely, if we reached the
-/// iteration limit without being able to prove that `c` is not a member),
-/// return `None`.
-fn escape_time_julia(z: Complex<f64>, c: Complex<f64>, limit: usize) -> Option<usize> {
+/// If `z` is not a member, return the `EscapeSample` describing how its orbit
+/// left the circle of radius `orbit.bailout` centered on the origin. If `z`
+/// seems to be a member (more precisely, if we reached the iteration limit
+/// without being able to prove that `z` is not a member), return
+/// `EscapeSample::INTERIOR`.
+fn escape_time_julia(z: Complex<f64>, c: Complex<f64>, limit: usize, orbit: Orbit) -> EscapeSample {
     let mut z = z;
+    let mut dz: Complex<f64> = Complex{ re: 1.0, im: 0.0 };
+    let radius_sqr = orbit.bailout * orbit.bailout;
     for i in 0..limit {
-        if z.norm_sqr() >= 4. {
-            return Some(i)
+        if z.norm_sqr() >= radius_sqr {
+            return EscapeSample::escaped(i, z, dz, orbit.power)
         }
-        z = z * z + c;
+        let (raised, slope) = orbit.raise(z);
+        dz = slope * dz;
+        z = raised + c;
+    }
+    EscapeSample::INTERIOR
+}
+
+/// The number of iterations after which a point is taken to be in the set.
+pub const LIMIT: usize = 255;
+
+/// The power orbits are raised to when none is given.
+pub const POWER: f64 = 2.0;
+
+/// The radius an orbit has to leave to escape when none is given.
+pub const BAILOUT: f64 = 2.0;
+
+/// How an orbit is followed: each step raises it to `power` and adds the
+/// constant, until it leaves the circle of radius `bailout` or runs out of
+/// iterations.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Orbit {
+    pub power: f64,
+    pub bailout: f64,
+}
+
+impl Default for Orbit {
+    fn default() -> Self {
+        Orbit { power: POWER, bailout: BAILOUT }
+    }
+}
+
+impl Orbit {
+    /// `z` raised to the power, and the derivative of that at `z`. Powers
+    /// that aren't whole numbers take the principal branch.
+    fn raise(&self, z: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+        if self.power == 2.0 {
+            return (z * z, 2.0 * z)
+        }
+        let below = if self.power.fract() == 0.0 && self.power <= i32::MAX as f64 {
+            z.powi(self.power as i32 - 1)
+        } else {
+            z.powf(self.power - 1.0)
+        };
+        (below * z, self.power * below)
+    }
+
+    /// Whether the points that stay inside form a set without holes, as
+    /// they do for any whole power, whatever the bailout.
+    pub fn is_full(&self) -> bool {
+        self.power.fract() == 0.0
+    }
+
+    /// Check that the power and bailout make sense, explaining what is wrong
+    /// if they don't.
+    pub fn check(&self) -> Result<(), String> {
+        if !(self.power.is_finite() && self.power > 1.0) {
+            return Err(format!("the power has to be above 1, not {}", self.power))
+        }
+        if !(self.bailout.is_finite() && self.bailout > 1.0) {
+            return Err(format!("the bailout has to be above 1, not {}", self.bailout))
+        }
+        Ok(())
+    }
+}
+
+/// The Julia seed used when none is given.
+pub const DEFAULT_SEED: Complex<f64> = Complex { re: 0.4, im: 0.6 };
+
+/// The fractals we know how to render.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fractal {
+    Mandelbrot,
+    BurningShip,
+    /// The Julia set with the given seed.
+    Julia(Complex<f64>),
+}
+
+impl Fractal {
+    /// Iterate the point `point` of the image plane for this fractal, at
+    /// most `limit` times.
+    pub fn escape(self, point: Complex<f64>, limit: usize, orbit: Orbit) -> EscapeSample {
+        match self {
+            Fractal::Mandelbrot => escape_time_mandel(point, limit, orbit),
+            Fractal::BurningShip => escape_time_burningship(point, limit, orbit),
+            Fractal::Julia(seed) => escape_time_julia(point, seed, limit, orbit),
+        }
+    }
+
+    /// Whether the points that survive any number of iterations form a set
+    /// without holes, which is what `accel` needs to skip the interior. The
+    /// burning ship's folding breaks this.
+    pub fn is_full(self) -> bool {
+        !matches!(self, Fractal::BurningShip)
     }
-    None
 }
 
 /// Parse the string `s` as a coordinate pair, like `"400x600"` or `"1.0,0.5"`.
@@ -76,7 +179,47 @@ pub fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)> {
 /// Parse a pair of floating-point numbers separated by a comma as a complex 
 /// number.
 pub fn parse_complex(s: &str) -> Option<Complex<f64>> {
-    parse_pair(s, ',').and_then(|(re, im)| Some(Complex { re, im }))
+    parse_pair(s, ',').map(|(re, im)| Complex { re, im })
+}
+
+/// Parse an image size like `"1920x1080"`, neither side of which may be zero.
+pub fn parse_bounds(s: &str) -> Result<(usize, usize), Error> {
+    match parse_pair(s, 'x') {
+        Some((width, height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(Error::BadDimensions(s.to_string())),
+    }
+}
+
+/// `parse_complex`, failing with an explanation.
+pub fn parse_point(s: &str) -> Result<Complex<f64>, Error> {
+    parse_complex(s).ok_or_else(|| Error::BadComplex(s.to_string()))
+}
+
+/// Check that `upper_left` and `lower_right` are the corners of a region
+/// with some area.
+pub fn check_viewport(upper_left: Complex<f64>, lower_right: Complex<f64>) -> Result<(), Error> {
+    // written so that NaNs fail too
+    if lower_right.re - upper_left.re > 0.0 && upper_left.im - lower_right.im > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::EmptyViewport(upper_left, lower_right))
+    }
+}
+
+/// Grow the region between `upper_left` and `lower_right` about its center,
+/// across or down, until it has the same shape as an image of size `bounds`,
+/// so that its pixels are square. Returns the new corners.
+pub fn fit_viewport(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> (Complex<f64>, Complex<f64>) {
+    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+    let ratio = bounds.0 as f64 / bounds.1 as f64;
+    let (width, height) = if width / height < ratio {
+        (height * ratio, height)
+    } else {
+        (width, width / ratio)
+    };
+    let center = (upper_left + lower_right) / 2.0;
+    let half = Complex { re: width / 2.0, im: -height / 2.0 };
+    (center - half, center + half)
 }
 
 /// Given the row and column of a pixel in the output image, return the
@@ -90,108 +233,42 @@ pub fn pixel_to_point(bounds: (usize, usize),
                   pixel: (usize, usize),
                   upper_left: Complex<f64>,
                   lower_right: Complex<f64>) -> Complex<f64> {
+    subpixel_to_point(bounds, (pixel.0 as f64, pixel.1 as f64), upper_left, lower_right)
+}
+
+/// Like `pixel_to_point`, but for a position anywhere inside the image rather
+/// than just at the corners of its pixels.
+pub fn subpixel_to_point(bounds: (usize, usize),
+                  pixel: (f64, f64),
+                  upper_left: Complex<f64>,
+                  lower_right: Complex<f64>) -> Complex<f64> {
     let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
     Complex {
-        re: upper_left.re + pixel.0 as f64 * width / bounds.0 as f64,
-        im: upper_left.im - pixel.1 as f64 * height / bounds.1 as f64
-    }
-}
-
-/// Render a rectangle of the Julia set into a buffer of pixels.
-/// 
-/// The `bounds` argument gives the width and height of the buffer `pixels`,
-/// which holds one grayscale pixel per byte. the `upper_left` and `lower_right`
-/// arguments specify points on the complex plane corresponding to the upper-
-/// left and lower-right corners of the pixel buffer. 
-pub fn render_julia(pixels: &mut [u8],
-          bounds: (usize, usize),
-          upper_left: Complex<f64>,
-          lower_right: Complex<f64>,
-          seed: Complex<f64>,
-          colors: &[Color]) {
-    assert!(pixels.len() == bounds.0 * 3 * bounds.1);
-    for row in 0..bounds.1 {
-        for col in (0..bounds.0 * 3).step_by(3) {
-            let point = pixel_to_point(bounds, (col / 3, row), upper_left, lower_right);
-            let pix = row * bounds.0 * 3 + col;
-            let cur_color: Color = match escape_time_julia(point, seed, 255) {
-                None => Color(0, 0, 0),
-                Some(count) => color(colors, count),
-            };
-            match cur_color {
-                Color(r, g, b) => {
-                    pixels[pix] = r;
-                    pixels[pix+1] = g;
-                    pixels[pix+2] = b;
-                }
-            }
-        }
+        re: upper_left.re + pixel.0 * width / bounds.0 as f64,
+        im: upper_left.im - pixel.1 * height / bounds.1 as f64
     }
 }
 
-/// Render a rectangle of the Mandelbrot set into a buffer of pixels.
-/// 
-/// The `bounds` argument gives the width and height of the buffer `pixels`,
-/// which holds one grayscale pixel per byte. the `upper_left` and `lower_right`
-/// arguments specify points on the complex plane corresponding to the upper-
-/// left and lower-right corners of the pixel buffer. 
-pub fn render_mandel(pixels: &mut [u8],
-                     bounds: (usize, usize),
-                     upper_left: Complex<f64>,
-                     lower_right: Complex<f64>,
-                     colors: &[Color]) {
-    assert!(pixels.len() == bounds.0 * 3 * bounds.1);
-    for row in 0..bounds.1 {
-        for col in (0..bounds.0 * 3).step_by(3) {
-            let point = pixel_to_point(bounds, (col / 3, row), upper_left, lower_right);
-            let pix = row * bounds.0 * 3 + col;
-            let cur_color: Color = match escape_time_mandel(point, 255) {
-                None => Color(0, 0, 0),
-                Some(count) => color(colors, count),
-            };
-            match cur_color {
-                Color(r, g, b) => {
-                    pixels[pix] = r;
-                    pixels[pix+1] = g;
-                    pixels[pix+2] = b;
-                }
-            }
-        }
+/// Turn `point` `degrees` counterclockwise about the center of the region
+/// between `upper_left` and `lower_right`. A view turned by `degrees` shows
+/// at each pixel the point its unturned region has there, turned like this.
+pub fn rotate(point: Complex<f64>, degrees: f64, upper_left: Complex<f64>, lower_right: Complex<f64>) -> Complex<f64> {
+    if degrees == 0.0 {
+        return point
     }
+    let center = (upper_left + lower_right) / 2.0;
+    center + (point - center) * Complex::from_polar(1.0, degrees.to_radians())
 }
 
-pub fn render_burningship(pixels: &mut [u8],
-                     bounds: (usize, usize),
-                     upper_left: Complex<f64>,
-                     lower_right: Complex<f64>,
-                     colors: &[Color]) {
-    assert!(pixels.len() == bounds.0 * 3 * bounds.1);
-    for row in 0..bounds.1 {
-        for col in (0..bounds.0 * 3).step_by(3) {
-            let point = pixel_to_point(bounds, (col / 3, row), upper_left, lower_right);
-            let pix = row * bounds.0 * 3 + col;
-            let cur_color: Color = match escape_time_burningship(point, 255) {
-                None => Color(0, 0, 0),
-                Some(count) => color(colors, count),
-            };
-            match cur_color {
-                Color(r, g, b) => {
-                    pixels[pix] = r;
-                    pixels[pix+1] = g;
-                    pixels[pix+2] = b;
-                }
-            }
-        }
-    }
-                     }
-
-/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the
-/// file named `filename`.
-pub fn write_image(pixels: &[u8], bounds: (usize, usize), filename: &str) -> ImageResult<()> {
-    let output = File::create(filename)?;
-    let encoder = PngEncoder::new(output);
-    encoder.encode(&pixels, bounds.0 as u32, bounds.1 as u32, ColorType::Rgb8)?;
-    Ok(())
+/// The point at `pixel`, anywhere inside an image of size `bounds` showing
+/// the region between `upper_left` and `lower_right` turned `rotation`
+/// degrees counterclockwise about its center.
+pub fn view_point(bounds: (usize, usize),
+                  pixel: (f64, f64),
+                  upper_left: Complex<f64>,
+                  lower_right: Complex<f64>,
+                  rotation: f64) -> Complex<f64> {
+    rotate(subpixel_to_point(bounds, pixel, upper_left, lower_right), rotation, upper_left, lower_right)
 }
 
 #[cfg(test)]
@@ -214,4 +291,51 @@ mod test {
         assert_eq!(parse_complex("0.5,-10.32"), Some(Complex { re: 0.5, im: -10.32 }));
         assert_eq!(parse_complex("0.2,"), None);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_bounds() {
+        assert_eq!(parse_bounds("300x200").unwrap(), (300, 200));
+        assert!(matches!(parse_bounds("0x200"), Err(Error::BadDimensions(_))));
+        assert!(matches!(parse_bounds("300"), Err(Error::BadDimensions(_))));
+    }
+
+    #[test]
+    fn test_viewport() {
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        assert!(check_viewport(upper_left, lower_right).is_ok());
+        assert!(check_viewport(upper_left, Complex { re: 1.0, im: 1.0 }).is_err());
+        assert!(check_viewport(lower_right, upper_left).is_err());
+
+        // too narrow for the image, so it widens
+        assert_eq!(fit_viewport((400, 200), upper_left, lower_right), (Complex { re: -2.5, im: 1.0 }, Complex { re: 1.5, im: -1.0 }));
+        // too wide, so it grows taller
+        assert_eq!(fit_viewport((300, 300), upper_left, lower_right), (Complex { re: -2.0, im: 1.5 }, Complex { re: 1.0, im: -1.5 }));
+        assert_eq!(fit_viewport((300, 200), upper_left, lower_right), (upper_left, lower_right));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let (upper_left, lower_right) = (Complex { re: -1.0, im: 2.0 }, Complex { re: 1.0, im: 0.0 });
+        let point = Complex { re: 1.0, im: 1.0 };
+        assert_eq!(rotate(point, 0.0, upper_left, lower_right), point);
+        assert!((rotate(point, 90.0, upper_left, lower_right) - Complex { re: 0.0, im: 2.0 }).norm() < 1e-12);
+        assert!((rotate(point, -90.0, upper_left, lower_right) - Complex { re: 0.0, im: 0.0 }).norm() < 1e-12);
+    }
+
+    #[test]
+    fn test_orbit_power() {
+        let square = Orbit::default();
+        let cube = Orbit { power: 3.0, ..Orbit::default() };
+        // the cubic set reaches further along the positive real axis
+        let point = Complex { re: 0.3, im: 0.0 };
+        assert!(Fractal::Mandelbrot.escape(point, LIMIT, cube).interior);
+        assert!(!Fractal::Mandelbrot.escape(point, LIMIT, square).interior);
+        // a fractional power agrees with the whole one it is next to
+        let almost = Orbit { power: 3.0 + 1e-12, ..Orbit::default() };
+        let point = Complex { re: 0.4, im: 0.7 };
+        assert!((Fractal::Mandelbrot.escape(point, LIMIT, almost).smooth - Fractal::Mandelbrot.escape(point, LIMIT, cube).smooth).abs() < 1e-6);
+        assert!(cube.is_full() && !almost.is_full());
+        assert!(Orbit { power: 1.0, ..Orbit::default() }.check().is_err());
+        assert!(Orbit { bailout: f64::NAN, ..Orbit::default() }.check().is_err());
+    }
+}
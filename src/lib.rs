@@ -0,0 +1,37 @@
+//! Renders the Mandelbrot set, its Julia sets and the burning ship fractal.
+//!
+//! `RenderRequest` is the place to start: it renders a whole image in
+//! memory, as an `image::RgbImage` or as the escape samples behind it. The
+//! modules underneath are what the `mandelbrot` command line is built from,
+//! for rendering in tiles, streaming to a file, tile pyramids and the rest.
+//! A `Scene` is a `RenderRequest` and where to write it, in a form that can
+//! be saved as TOML or JSON, and `animate` turns requests into frames of
+//! video. `expmap` renders a whole zoom into one strip, to be made into
+//! frames afterwards, `preview` draws small renders in the terminal, and
+//! `explore` looks around a fractal there interactively.
+
+pub mod accel;
+pub mod animate;
+pub mod antialias;
+pub mod checkpoint;
+pub mod color;
+pub mod error;
+pub mod escape;
+pub mod expmap;
+pub mod explore;
+pub mod fractal;
+mod monocub;
+pub mod output;
+pub mod preview;
+pub mod progress;
+pub mod pyramid;
+pub mod request;
+pub mod scene;
+pub mod scheduler;
+pub mod stream;
+
+pub use error::{Error, Result};
+pub use escape::{EscapeField, EscapeSample};
+pub use fractal::Fractal;
+pub use request::RenderRequest;
+pub use scene::Scene;
@@ -1,16 +1,43 @@
-use clap::{App, Arg, ArgMatches};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{App, AppSettings, Arg, ArgMatches};
 use num::Complex;
-use rayon::prelude::*;
 
-mod color;
-mod fractal;
-mod monocub;
+use mandelbrot::{accel, animate, antialias, checkpoint, color, error, explore, output, preview, progress, pyramid, scheduler, stream};
+use mandelbrot::animate::{frame_filename, render_frames, Animation, Cycle, Interpolation, Inset, Keyframes, MotionBlur, SeedAnimation, SeedPath, VideoFormat, VideoOptions, VideoWriter, Zoom};
+use mandelbrot::antialias::Antialias;
+use mandelbrot::checkpoint::{Checkpoint, RenderParams};
+use mandelbrot::color::ColorMode;
+use mandelbrot::explore::{Bookmarks, Explorer};
+use mandelbrot::expmap::{ExpMap, Reprojection};
+use mandelbrot::fractal::{check_viewport, fit_viewport, parse_bounds, parse_point, Orbit, DEFAULT_SEED, LIMIT};
+use mandelbrot::output::{write_image, OutputOptions};
+use mandelbrot::preview::{Preview, PreviewFormat};
+use mandelbrot::progress::{Progress, ProgressStyle};
+use mandelbrot::pyramid::Pyramid;
+use mandelbrot::scene::{ColorSpec, FractalSpec, OutputSpec, View};
+use mandelbrot::scheduler::{Scheduler, Tile};
+use mandelbrot::stream::StreamRender;
+use mandelbrot::{Error, EscapeField, Fractal, RenderRequest, Result, Scene};
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
 
-use fractal::{parse_complex, parse_pair, pixel_to_point, render_julia, render_mandel, render_burningship, write_image};
+const VERSION: &str = "1.2.1";
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn run() -> Result<()> {
+    let help = HelpText::new();
     let matches: ArgMatches = App::new("mandelbrot")
-                          .version("1.2.1")
+                          .version(VERSION)
                           .author("Brent Mode <bmode@wisc.edu")
                           .about("creates mandelbrot and julia set images")
                           .subcommand(App::new("julia")
@@ -27,18 +54,27 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                       .arg(Arg::new("LOWERRIGHT")
                                            .about("Set the lower right corner of the complex plane\nEx: 2.0,-2.0    (2 - 2i)")
                                            .required(true))
-                                      .arg(Arg::new("COLORSCHEME")
-                                           .short('c')
-                                           .long("color")
-                                           .about(&*(format!("Set the color scheme from {}", color::COLORLIST)))
-                                           .takes_value(true)
-                                           .required(false))
                                       .arg(Arg::new("SEED")
                                            .short('s')
                                            .long("seed")
                                            .about("Set the seed for the Julia set image\nEx: -0.4,0.6    (-0.4 + 0.6i)")
                                            .takes_value(true)
                                            .required(false))
+                                      .arg(Arg::new("FIELD")
+                                           .short('f')
+                                           .long("field")
+                                           .about("Also save the escape data to this file, for use with `recolor`\nEx: julia.mbef")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(scheduler_opts(&help))
+                                      .args(coloring_opts(&help))
+                                      .args(antialias_opts(&help))
+                                      .args(output_opts(&help))
+                                      .args(stream_opts())
+                                      .args(checkpoint_opts())
+                                      .args(progress_opts(&help))
+                                      .args(preview_opts(&help, true))
+                                      .arg(dump_scene_opt())
                                       .after_help("Full example:\nmandelbrot julia --color=vaportest --seed=-0.4,0.6 -- julia.png 5000x5000 -2,2 2,-2"))
                           .subcommand(App::new("mandel")
                                       .about("creates a mandelbrot set image")
@@ -54,127 +90,1464 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                                       .arg(Arg::new("LOWERRIGHT")
                                            .about("Set the lower right corner of the complex plane\nEx: -1,0.20    (-1 + 0.2i)")
                                            .required(true))
-                                      .arg(Arg::new("COLORSCHEME")
-                                           .short('c')
-                                           .long("color")
-                                           .about(&*(format!("Set the color scheme from:\n{}", color::COLORLIST)))
-                                           .takes_value(true)
-                                           .required(false))
                                       .arg(Arg::new("ALTFN")
                                            .short('a')
                                            .long("altfn")
                                            .about("For now, just uses the burning ship fractal in place of the Mandelbrot fractal")
                                            .required(false))
+                                      .arg(Arg::new("FIELD")
+                                           .short('f')
+                                           .long("field")
+                                           .about("Also save the escape data to this file, for use with `recolor`\nEx: mandel.mbef")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(scheduler_opts(&help))
+                                      .args(coloring_opts(&help))
+                                      .args(antialias_opts(&help))
+                                      .args(output_opts(&help))
+                                      .args(stream_opts())
+                                      .args(checkpoint_opts())
+                                      .args(progress_opts(&help))
+                                      .args(preview_opts(&help, true))
+                                      .arg(dump_scene_opt())
                                       .after_help("Full example:\nmandelbrot mandel --color=vaportest --altfn -- bs.png 5000x5000 -2,2 2,-2"))
-                          .after_help("Full example:\nmandelbrot julia --color=vaportest --seed=-0.4,0.6 -- julia.png 5000x5000 -2,2 2,-2").get_matches();
-
-    if let Some(ref matches) = matches.subcommand_matches("julia") {
-        let (file, bounds, upper_left, lower_right, colors) = common_args(*matches);
+                          .subcommand(App::new("render")
+                                      .about("renders the image described by a scene file")
+                                      .arg(Arg::new("SCENE")
+                                           .about("Set the scene file to read, which is JSON if it ends in .json and TOML otherwise\nEx: julia.toml")
+                                           .required(true))
+                                      .arg(Arg::new("FILE")
+                                           .about("Write the image here instead of to the file the scene names\nEx: julia-again.png")
+                                           .required(false))
+                                      .arg(Arg::new("FIELD")
+                                           .short('f')
+                                           .long("field")
+                                           .about("Also save the escape data to this file, instead of where the scene says\nEx: julia.mbef")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(stream_opts())
+                                      .args(checkpoint_opts())
+                                      .args(progress_opts(&help))
+                                      .args(preview_opts(&help, true))
+                                      .after_help("Scene files are written by --dump-scene, and can be edited by hand.\n\n\
+                                                   Full example:\nmandelbrot julia --dump-scene=julia.toml -- julia.png 5000x5000 -2,2 2,-2\n\
+                                                   mandelbrot render julia.toml"))
+                          .subcommand(App::new("rerender")
+                                      .about("renders an image again from the settings saved in it, at any size")
+                                      .arg(Arg::new("IMAGE")
+                                           .about("Set the png image to read the settings from\nEx: julia.png")
+                                           .required(true))
+                                      .arg(Arg::new("FILE")
+                                           .about("Set the file name\nEx: julia-big.png")
+                                           .required(true))
+                                      .arg(Arg::new("SIZE")
+                                           .long("size")
+                                           .about("Set the image size instead of keeping the original one; a different\nshape shows more of the plane\nEx: 8000x6000")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(Arg::new("FIELD")
+                                           .short('f')
+                                           .long("field")
+                                           .about("Also save the escape data to this file, for use with `recolor`\nEx: julia-big.mbef")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(stream_opts())
+                                      .args(checkpoint_opts())
+                                      .args(progress_opts(&help))
+                                      .args(preview_opts(&help, true))
+                                      .arg(dump_scene_opt())
+                                      .after_help("Full example:\nmandelbrot rerender --size=7680x4320 -- julia.png julia-8k.png"))
+                          .subcommand(App::new("preview")
+                                      .about("draws a small version of a scene or image in the terminal")
+                                      .arg(Arg::new("SCENE")
+                                           .about("Set the scene file, or png image saying how it was rendered, to preview\nEx: julia.toml")
+                                           .required(true))
+                                      .args(preview_opts(&help, false))
+                                      .after_help("Full example:\nmandelbrot preview --columns=120 julia.png"))
+                          .subcommand(App::new("explore")
+                                      .about("looks around a fractal in the terminal, rendering views worth keeping at full size")
+                                      .arg(Arg::new("SCENE")
+                                           .about("Set the scene file, or png image saying how it was rendered, to start from\nEx: julia.toml")
+                                           .required(false))
+                                      .arg(Arg::new("OUTPUT")
+                                           .short('o')
+                                           .long("output")
+                                           .about("Set the files full size renders go to, with %d numbering them (default explore-%d.png)")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(Arg::new("SIZE")
+                                           .long("size")
+                                           .about("Set the size of full size renders (default 1920x1080, or the scene's)")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(Arg::new("BOOKMARKS")
+                                           .long("bookmarks")
+                                           .about("Set the file bookmarks are kept in (default bookmarks.toml)")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(fractal_opts())
+                                      .arg(iterations_opt())
+                                      .args(coloring_opts(&help))
+                                      .args(output_opts(&help))
+                                      .after_help(&*help.explore_keys))
+                          .subcommand(App::new("info")
+                                      .about("prints the settings saved in a png image")
+                                      .arg(Arg::new("IMAGE")
+                                           .about("Set the png image to read\nEx: julia.png")
+                                           .required(true)))
+                          .subcommand(App::new("animate")
+                                      .about("renders animations, as numbered images or uncompressed video")
+                                      .setting(AppSettings::SubcommandRequiredElseHelp)
+                                      .subcommand(App::new("zoom")
+                                                  .about("zooms from a region of the plane into a point")
+                                                  .arg(Arg::new("OUTPUT")
+                                                       .about("Set the frame file names, with %d or %05d where the frame number goes,\nor else the video file, or - to write the video to stdout\nEx: frames/zoom-%05d.png")
+                                                       .required(true))
+                                                  .arg(Arg::new("PIXELS")
+                                                       .about("Set the frame size\nEx: 1920x1080")
+                                                       .required(true))
+                                                  .arg(Arg::new("UPPERLEFT")
+                                                       .about("Set the upper left corner of the first frame\nEx: -2.5,1.2    (-2.5 + 1.2i)")
+                                                       .required(true))
+                                                  .arg(Arg::new("LOWERRIGHT")
+                                                       .about("Set the lower right corner of the first frame\nEx: 1.0,-1.2    (1 - 1.2i)")
+                                                       .required(true))
+                                                  .arg(Arg::new("TARGET")
+                                                       .about("Set the point to zoom into, which is the center of the last frame\nEx: -0.743643887,0.131825904")
+                                                       .required(true))
+                                                  .arg(Arg::new("ZOOM")
+                                                       .about("Set how many times narrower the last frame is than the first\nEx: 1e9")
+                                                       .required(true))
+                                                  .args(animation_opts(&help))
+                                                  .arg(Arg::new("GROWTH")
+                                                       .long("grow-iterations")
+                                                       .about("Add this many iterations to the limit for every tenfold zoom (default 0)\nEx: 200")
+                                                       .takes_value(true)
+                                                       .required(false))
+                                                  .args(fractal_opts())
+                                                  .args(scheduler_opts(&help))
+                                                  .args(coloring_opts(&help))
+                                                  .args(antialias_opts(&help))
+                                                  .args(output_opts(&help))
+                                                  .arg(progress_opts(&help).remove(0))
+                                                  .after_help("Full example:\nmandelbrot animate zoom --frames=600 --grow-iterations=200 --mode=smooth -- \\\n    - 1280x720 -2.5,1.2 1.5,-1.2 -0.743643887,0.131825904 1e9 | ffmpeg -i - zoom.mp4"))
+                                      .subcommand(App::new("cycle")
+                                                  .about("cycles the palette of a single render")
+                                                  .arg(Arg::new("OUTPUT")
+                                                       .about("Set the frame file names, with %d or %05d where the frame number goes,\nor else the video file, or - to write the video to stdout\nEx: cycle.gif")
+                                                       .required(true))
+                                                  .arg(Arg::new("PIXELS")
+                                                       .about("Set the frame size\nEx: 640x480")
+                                                       .required(true))
+                                                  .arg(Arg::new("UPPERLEFT")
+                                                       .about("Set the upper left corner of the region\nEx: -2.5,1.2    (-2.5 + 1.2i)")
+                                                       .required(true))
+                                                  .arg(Arg::new("LOWERRIGHT")
+                                                       .about("Set the lower right corner of the region\nEx: 1.0,-1.2    (1 - 1.2i)")
+                                                       .required(true))
+                                                  .args(animation_opts(&help))
+                                                  .arg(Arg::new("CYCLES")
+                                                       .long("cycles")
+                                                       .about("Set how many times the palette goes round over the animation, where a whole\nnumber loops seamlessly and a negative one turns the other way (default 1)")
+                                                       .takes_value(true)
+                                                       .required(false))
+                                                  .args(fractal_opts())
+                                                  .args(scheduler_opts(&help))
+                                                  .args(coloring_opts(&help))
+                                                  .args(antialias_opts(&help))
+                                                  .args(output_opts(&help))
+                                                  .arg(progress_opts(&help).remove(0))
+                                                  .after_help("The fractal is rendered once, and every frame colors it again with the palette\nturned a little further, so it takes little longer than a single image.\n\n\
+                                                               Full example:\nmandelbrot animate cycle --frames=60 --fps=20 --mode=smooth -- cycle.gif 480x360 -2.5,1.5 1.5,-1.5"))
+                                      .subcommand(App::new("julia")
+                                                  .about("animates a julia set with its seed moving along a path")
+                                                  .arg(Arg::new("OUTPUT")
+                                                       .about("Set the frame file names, with %d or %05d where the frame number goes,\nor else the video file, or - to write the video to stdout\nEx: julia.gif")
+                                                       .required(true))
+                                                  .arg(Arg::new("PIXELS")
+                                                       .about("Set the frame size\nEx: 640x480")
+                                                       .required(true))
+                                                  .arg(Arg::new("UPPERLEFT")
+                                                       .about("Set the upper left corner of the region\nEx: -2,1.5    (-2 + 1.5i)")
+                                                       .required(true))
+                                                  .arg(Arg::new("LOWERRIGHT")
+                                                       .about("Set the lower right corner of the region\nEx: 2,-1.5    (2 - 1.5i)")
+                                                       .required(true))
+                                                  .arg(Arg::new("PATH")
+                                                       .about(&help.seed_paths)
+                                                       .required(true))
+                                                  .args(animation_opts(&help))
+                                                  .arg(Arg::new("INSET")
+                                                       .long("inset")
+                                                       .about("Draw a map of the Mandelbrot set in the corner, marking where the seed is")
+                                                       .required(false))
+                                                  .args(scheduler_opts(&help))
+                                                  .args(coloring_opts(&help))
+                                                  .args(antialias_opts(&help))
+                                                  .args(output_opts(&help))
+                                                  .arg(progress_opts(&help).remove(0))
+                                                  .after_help("A path that ends where it starts loops seamlessly.\n\n\
+                                                               Full example:\nmandelbrot animate julia --frames=120 --inset --mode=smooth -- julia.gif 480x360 -2,1.5 2,-1.5 cardioid=0.98"))
+                                      .subcommand(App::new("keyframes")
+                                                  .about("animates a scene between keyframes")
+                                                  .arg(Arg::new("SCENE")
+                                                       .about("Set the scene file to animate, which is JSON if it ends in .json and TOML otherwise\nEx: julia.toml")
+                                                       .required(true))
+                                                  .arg(Arg::new("KEYFRAMES")
+                                                       .about("Set the keyframe file, which is JSON if it ends in .json and TOML otherwise\nEx: julia-keys.toml")
+                                                       .required(true))
+                                                  .arg(Arg::new("OUTPUT")
+                                                       .about("Set the frame file names, with %d or %05d where the frame number goes,\nor else the video file, or - to write the video to stdout\nEx: frames/julia-%05d.png")
+                                                       .required(true))
+                                                  .args(animation_opts(&help))
+                                                  .arg(Arg::new("INTERPOLATION")
+                                                       .short('i')
+                                                       .long("interpolation")
+                                                       .about(&help.interpolations)
+                                                       .takes_value(true)
+                                                       .required(false))
+                                                  .arg(progress_opts(&help).remove(0))
+                                                  .after_help("Keyframes can set center, zoom, seed, palette_offset, iterations,\nrotation (in degrees, counterclockwise), power and bailout,\nand are in a file like:\n\n\
+                                                               interpolation = \"monotonic\"\n\n\
+                                                               [[keyframe]]\nframe = 0\nseed = [-0.8, 0.156]\n\n\
+                                                               [[keyframe]]\nframe = 239\nseed = [-0.7, 0.27]\nzoom = 4.0\npalette_offset = 1.0\nrotation = 90.0\n\n\
+                                                               Full example:\nmandelbrot animate keyframes julia.toml julia-keys.toml frames/julia-%04d.png"))
+                                      .subcommand(App::new("reproject")
+                                                  .about("zooms into the center of an exponential map made by expmap")
+                                                  .arg(Arg::new("STRIP")
+                                                       .about("Set the exponential map to read\nEx: zoom.mbem")
+                                                       .required(true))
+                                                  .arg(Arg::new("OUTPUT")
+                                                       .about("Set the frame file names, with %d or %05d where the frame number goes,\nor else the video file, or - to write the video to stdout\nEx: frames/zoom-%05d.png")
+                                                       .required(true))
+                                                  .arg(Arg::new("PIXELS")
+                                                       .about("Set the frame size\nEx: 1280x720")
+                                                       .required(true))
+                                                  .args(animation_opts(&help))
+                                                  .args(coloring_opts(&help))
+                                                  .args(output_opts(&help))
+                                                  .arg(progress_opts(&help).remove(0))
+                                                  .after_help("The first frame has its corners on the outer edge of the map, and the last\nreaches as deep as the map goes. Nothing is rendered, so it is quick, and frames\nare sharp when the map is at least pi times the frame diagonal in samples round.\n\n\
+                                                               Full example:\nmandelbrot animate reproject --frames=600 --mode=smooth -- zoom.mbem zoom.y4m 1280x720")))
+                          .subcommand(App::new("expmap")
+                                      .about("renders an exponential map, a strip holding a whole zoom, for animate reproject")
+                                      .arg(Arg::new("FILE")
+                                           .about("Set the file to write the map to\nEx: zoom.mbem")
+                                           .required(true))
+                                      .arg(Arg::new("WIDTH")
+                                           .about("Set how many samples go round the center, which is the width of the strip\nEx: 4096")
+                                           .required(true))
+                                      .arg(Arg::new("CENTER")
+                                           .about("Set the point to zoom into\nEx: -0.743643887,0.131825904")
+                                           .required(true))
+                                      .arg(Arg::new("RADIUS")
+                                           .about("Set how far from the center the strip starts\nEx: 2")
+                                           .required(true))
+                                      .arg(Arg::new("ZOOM")
+                                           .about("Set how many times closer to the center the strip ends\nEx: 1e9")
+                                           .required(true))
+                                      .args(fractal_opts())
+                                      .arg(iterations_opt())
+                                      .arg(Arg::new("IMAGE")
+                                           .long("image")
+                                           .about("Also write the strip as an image, colored with the coloring options\nEx: zoom-strip.png")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .args(coloring_opts(&help))
+                                      .args(output_opts(&help))
+                                      .arg(progress_opts(&help).remove(0))
+                                      .after_help("Across the strip is once round the center and down it is in towards it, by\nthe same factor every row, so a strip W samples wide is W*ln(ZOOM)/2pi rows tall.\n\n\
+                                                   Full example:\nmandelbrot expmap --iterations=2000 -- zoom.mbem 4096 -0.743643887,0.131825904 2 1e9"))
+                          .subcommand(App::new("recolor")
+                                      .about("colors a saved escape field without rendering it again")
+                                      .arg(Arg::new("FIELD")
+                                           .about("Set the escape field to read\nEx: mandel.mbef")
+                                           .required(true))
+                                      .arg(Arg::new("FILE")
+                                           .about("Set the file name\nEx: mandel.png")
+                                           .required(true))
+                                      .args(coloring_opts(&help))
+                                      .args(output_opts(&help))
+                                      .after_help("Full example:\nmandelbrot mandel --field=mandel.mbef -- mandel.png 5000x5000 -2,2 2,-2\nmandelbrot recolor --color=magma --mode=smooth -- mandel.mbef mandel-magma.png"))
+                          .subcommand(App::new("export-tiles")
+                                      .about("renders a tile pyramid for web map and deep zoom viewers")
+                                      .arg(Arg::new("OUTPUT")
+                                           .about("Set the directory for xyz tiles, or the .dzi file for deep zoom\nEx: tiles")
+                                           .required(true))
+                                      .arg(Arg::new("UPPERLEFT")
+                                           .about("Set the upper left corner of the complex plane\nEx: -2.0,1.5    (-2 + 1.5i)")
+                                           .required(true))
+                                      .arg(Arg::new("LOWERRIGHT")
+                                           .about("Set the lower right corner of the complex plane\nEx: 1.0,-1.5    (1 - 1.5i)")
+                                           .required(true))
+                                      .args(fractal_opts())
+                                      .arg(Arg::new("LAYOUT")
+                                           .long("layout")
+                                           .about(&help.layouts)
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(Arg::new("SIZE")
+                                           .long("size")
+                                           .about("Set the width and height of each tile in pixels (default 256)")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(Arg::new("MAXZOOM")
+                                           .short('z')
+                                           .long("max-zoom")
+                                           .about("Set the deepest zoom level, where the region is 2^zoom tiles across (default 5)")
+                                           .takes_value(true)
+                                           .required(false))
+                                      .arg(accel_opt(&help))
+                                      .arg(iterations_opt())
+                                      .args(coloring_opts(&help))
+                                      .arg(progress_opts(&help).remove(0))
+                                      .after_help("Tiles that already exist are skipped, so an interrupted export can be\nfinished by running the same command again.\n\nFull example:\nmandelbrot export-tiles --layout=dzi --max-zoom=8 -- mandel.dzi -2,1.5 1,-1.5"))
+                          .after_help(&*help.exit_codes).get_matches();
 
-        let seed: Complex<f64>;
-        if let Some(seed_str) = matches.value_of("SEED") {
-            seed = parse_complex(seed_str).unwrap();
+    if let Some(matches) = matches.subcommand_matches("julia") {
+        let seed: Complex<f64> = if let Some(seed_str) = matches.value_of("SEED") {
+            parse_point(seed_str)?
         } else {
-            seed = Complex{ re: 0.4, im: 0.6 };
+            DEFAULT_SEED
+        };
+        let args = common_args(matches, scene_args(matches, Fractal::Julia(seed))?)?;
+        progress::catch_interrupt()?;
+        render(&args)
+    } else if let Some(matches) = matches.subcommand_matches("mandel") {
+        let altfn = matches.occurrences_of("ALTFN") > 0;
+        let args = common_args(matches, scene_args(matches, if altfn { Fractal::BurningShip } else { Fractal::Mandelbrot })?)?;
+        progress::catch_interrupt()?;
+        render(&args)
+    } else if let Some(matches) = matches.subcommand_matches("render") {
+        let mut scene = Scene::read(matches.value_of("SCENE").unwrap())?;
+        if let Some(file) = matches.value_of("FILE") {
+            scene.output.file = file.to_string();
+        }
+        if let Some(field) = matches.value_of("FIELD") {
+            scene.output.field = Some(field.to_string());
+        }
+        render_scene(matches, scene)
+    } else if let Some(matches) = matches.subcommand_matches("rerender") {
+        let mut scene = Scene::from_image(matches.value_of("IMAGE").unwrap())?;
+        if let Some(size) = matches.value_of("SIZE") {
+            scene.view.size = parse_bounds(size)?.into();
+        }
+        // the new file's extension picks its format, and the old field is
+        // left alone
+        scene.output.file = matches.value_of("FILE").unwrap().to_string();
+        scene.output.format = None;
+        scene.output.field = matches.value_of("FIELD").map(str::to_string);
+        render_scene(matches, scene)
+    } else if let Some(matches) = matches.subcommand_matches("preview") {
+        let path = matches.value_of("SCENE").unwrap();
+        let scene = if path.to_ascii_lowercase().ends_with(".png") { Scene::from_image(path)? } else { Scene::read(path)? };
+        let request = scene.to_request()?;
+        preview_args(matches)?.print(&mut io::stdout().lock(), &request)
+    } else if let Some(matches) = matches.subcommand_matches("explore") {
+        explore(matches)
+    } else if let Some(matches) = matches.subcommand_matches("info") {
+        let image = matches.value_of("IMAGE").unwrap();
+        let text = output::read_png_text(image).map_err(|e| Error::from(e).in_file(image))?;
+        if text.is_empty() {
+            println!("{} has no text chunks", image);
         }
-        create_julia(file, bounds, upper_left, lower_right, seed, colors);
-    } else if let Some(ref matches) = matches.subcommand_matches("mandel") {
-        let (file, bounds, upper_left, lower_right, colors) = common_args(*matches);
-        if matches.occurrences_of("ALTFN") > 0 {
-            create_mandel(file, bounds, upper_left, lower_right, colors, true);
+        for (keyword, text) in text {
+            if text.contains('\n') {
+                println!("{}:\n{}", keyword, text.trim_end());
+            } else {
+                println!("{}: {}", keyword, text);
+            }
+        }
+        Ok(())
+    } else if let Some(matches) = matches.subcommand_matches("animate") {
+        progress::catch_interrupt()?;
+        match matches.subcommand() {
+            Some(("zoom", matches)) => animate_zoom(matches),
+            Some(("keyframes", matches)) => animate_keyframes(matches),
+            Some(("cycle", matches)) => animate_cycle(matches),
+            Some(("julia", matches)) => animate_julia(matches),
+            Some(("reproject", matches)) => animate_reproject(matches),
+            _ => Ok(()),
+        }
+    } else if let Some(matches) = matches.subcommand_matches("recolor") {
+        let (field_file, file) = (matches.value_of("FIELD").unwrap(), matches.value_of("FILE").unwrap());
+        let field = EscapeField::read(field_file).map_err(|e| Error::from(e).in_file(field_file))?;
+        let pixels = color::colorize(&field, &color_args(matches)?.coloring()?);
+        write_image(&pixels, field.bounds, file, &output_args(matches)?).map_err(|e| Error::from(e).in_file(file))
+    } else if let Some(matches) = matches.subcommand_matches("expmap") {
+        progress::catch_interrupt()?;
+        expmap(matches)
+    } else if let Some(matches) = matches.subcommand_matches("export-tiles") {
+        progress::catch_interrupt()?;
+        export_tiles(matches)
+    } else {
+        Ok(())
+    }
+}
+
+/// Render `scene`, from a scene file or an image, with the streaming,
+/// checkpoint and progress options in `matches`.
+fn render_scene(matches: &ArgMatches, mut scene: Scene) -> Result<()> {
+    if matches.occurrences_of("STREAM") > 0 {
+        if let Some(field) = scene.output.field.take() {
+            eprintln!("not saving the escape field to {}, a streamed render doesn't keep one", field);
+        }
+    }
+    let args = common_args(matches, scene)?;
+    progress::catch_interrupt()?;
+    render(&args)
+}
+
+/// The arguments shared by the `julia`, `mandel` and `render` subcommands.
+struct CommonArgs {
+    file: String,
+    field: Option<String>,
+    request: RenderRequest,
+    output: OutputOptions,
+    /// How many strips may be in flight, if the image is to be streamed.
+    stream: Option<usize>,
+    /// How often to sync the checkpoint, if one is kept.
+    checkpoint: Option<Duration>,
+    resume: bool,
+    progress: ProgressStyle,
+    /// Whether to write what was finished if the render is cancelled.
+    save_partial: bool,
+    /// A preview to draw in the terminal instead of rendering, if asked for.
+    preview: Option<Preview>,
+}
+
+/// The scene described by the arguments of the `julia` and `mandel`
+/// subcommands.
+fn scene_args(matches: &ArgMatches, fractal: Fractal) -> Result<Scene> {
+    let bounds = parse_bounds(matches.value_of("PIXELS").unwrap())?;
+    let mut upper_left = parse_point(matches.value_of("UPPERLEFT").unwrap())?;
+    let mut lower_right = parse_point(matches.value_of("LOWERRIGHT").unwrap())?;
+    check_viewport(upper_left, lower_right)?;
+    if let Some(region) = shift_region(bounds, upper_left, lower_right) {
+        (upper_left, lower_right) = region;
+        let notice = format!("NEW UPPERLEFT\t\t{}\nNEW LOWERRIGHT\t   {}", upper_left, lower_right);
+        // a preview is drawn on stdout, so the notice keeps out of its way
+        if matches.is_present("PREVIEW") {
+            eprintln!("{}", notice);
         } else {
-            create_mandel(file, bounds, upper_left, lower_right, colors, false);
+            println!("{}", notice);
         }
     }
+    Ok(Scene {
+        fractal: FractalSpec::new(fractal, Orbit::default()),
+        view: View::new(bounds, upper_left, lower_right),
+        render: scheduler_args(matches)?,
+        color: color_args(matches)?,
+        antialias: antialias_args(matches)?,
+        output: OutputSpec::new(matches.value_of("FILE").unwrap(), matches.value_of("FIELD"), &output_args(matches)?),
+    })
+}
 
-    Ok(())
+/// Write `scene` out if `--dump-scene` asks for it, and gather what else is
+/// needed to render it. PNG images get the scene written into them too.
+///
+/// The region is rendered just as the scene gives it, so that a dumped or
+/// embedded scene renders the same image again.
+fn common_args(matches: &ArgMatches, scene: Scene) -> Result<CommonArgs> {
+    let request = scene.to_request()?;
+    if let Some(path) = matches.value_of("DUMPSCENE") {
+        scene.write(path)?;
+    }
+
+    let mut output = scene.output.options();
+    output.text = scene.png_text(&format!("mandelbrot {}", VERSION));
+    Ok(CommonArgs {
+        output,
+        file: scene.output.file,
+        field: scene.output.field,
+        request,
+        stream: stream_args(matches)?,
+        checkpoint: checkpoint_args(matches)?,
+        resume: matches.occurrences_of("RESUME") > 0,
+        progress: progress_args(matches)?,
+        save_partial: matches.occurrences_of("PARTIAL") > 0,
+        preview: if matches.is_present("PREVIEW") { Some(preview_args(matches)?) } else { None },
+    })
+}
+
+/// The region given on the `julia` and `mandel` command lines, moved in
+/// along its longer side when it isn't the shape of the image. Returns
+/// `None` when it already is.
+///
+/// This is how those commands have always treated a mismatched region, and
+/// existing command lines rely on it to render the same image, so it is kept
+/// even though it doesn't make the pixels square.
+fn shift_region(bounds: (usize, usize), mut upper_left: Complex<f64>, mut lower_right: Complex<f64>) -> Option<(Complex<f64>, Complex<f64>)> {
+    let ratio = bounds.0 as f64 / bounds.1 as f64;
+    let plane_ratio = (lower_right.re - upper_left.re) / (upper_left.im - lower_right.im);
+    if ratio == plane_ratio {
+        return None
+    }
+    if ratio > 1.0 {
+        let ratio = 1.0 / ratio;
+        upper_left.im -= ratio / 2.0;
+        lower_right.im += ratio / 2.0;
+    } else {
+        upper_left.re += ratio / 2.0;
+        lower_right.re -= ratio / 2.0;
+    }
+    Some((upper_left, lower_right))
 }
 
-fn common_args<'a>(matches: &'a ArgMatches) -> (&'a str, (usize, usize), Complex<f64>, Complex<f64>, [color::Color; 2048]) {
-    let file: &'a str = matches.value_of("FILE").unwrap();
-    let bounds: (usize, usize) = parse_pair(matches
-        .value_of("PIXELS")
-        .unwrap(), 'x')
-        .expect("error parsing image dimensions");
-    let mut ratio: f64 = bounds.0 as f64 / bounds.1 as f64; // x / y
-    let mut upper_left = parse_complex(matches
-        .value_of("UPPERLEFT").unwrap())
-        .expect("error parsing upper left corner point");
-    let mut lower_right = parse_complex(matches
-        .value_of("LOWERRIGHT").unwrap())
-        .expect("error parsing lower right corner point");
-    
+/// Pixels have to be square, so grow the region of `request` to the shape of
+/// its image if it isn't already. Returns whether it had to. The animations
+/// and the explorer use this; `julia` and `mandel` use `shift_region`.
+fn fit_region(request: &mut RenderRequest) -> bool {
+    let (bounds, upper_left, lower_right) = (request.bounds, request.upper_left, request.lower_right);
+    let ratio = bounds.0 as f64 / bounds.1 as f64;
     let plane_ratio = (lower_right.re - upper_left.re) / (upper_left.im - lower_right.im);
-    if ratio != plane_ratio {
-        if ratio > 1.0 {
-            ratio = 1.0 / ratio;
-            upper_left.im = upper_left.im - ratio / 2.0;
-            lower_right.im = lower_right.im + ratio / 2.0;
-        } else { 
-            upper_left.re = upper_left.re + ratio / 2.0;
-            lower_right.re = lower_right.re - ratio / 2.0;
+    if (ratio / plane_ratio - 1.0).abs() <= 1e-9 {
+        return false
+    }
+    (request.upper_left, request.lower_right) = fit_viewport(bounds, upper_left, lower_right);
+    true
+}
+
+/// The value of the option `id`, if it was given, parsed as a `T`. Mistakes
+/// are reported against `option`, as it is written on the command line.
+fn parse_arg<T>(matches: &ArgMatches, id: &str, option: &'static str) -> Result<Option<T>>
+    where T: FromStr, T::Err: fmt::Display {
+    matches.value_of(id).map(|value| value.parse().map_err(|e: T::Err| Error::BadOption {
+        option,
+        value: value.to_string(),
+        reason: e.to_string(),
+    })).transpose()
+}
+
+fn dump_scene_opt<'a>() -> Arg<'a> {
+    Arg::new("DUMPSCENE")
+        .long("dump-scene")
+        .about("Also write every setting of the render to this scene file, for `render`,\nas JSON if it ends in .json and TOML otherwise\nEx: julia.toml")
+        .takes_value(true)
+        .required(false)
+}
+
+/// Help strings that are built at run time, and so have to outlive the `App`
+/// that borrows them.
+struct HelpText {
+    orders: String,
+    accels: String,
+    layouts: String,
+    progress: String,
+    colors: String,
+    modes: String,
+    alpha: String,
+    patterns: String,
+    filters: String,
+    depths: String,
+    formats: String,
+    videos: String,
+    interpolations: String,
+    seed_paths: String,
+    preview_formats: String,
+    explore_keys: String,
+    exit_codes: String,
+}
+
+impl HelpText {
+    fn new() -> HelpText {
+        HelpText {
+            orders: format!("Set the order tiles are rendered in, from:\n{}", scheduler::TILEORDERS),
+            accels: format!("Set how the inside of the set is skipped, from:\n{}\
+                             Both are heuristics: filaments thinner than a pixel can be filled over,\n\
+                             so the image may differ from iterating every pixel", accel::ACCELS),
+            layouts: format!("Set how the tiles are laid out, from:\n{}", pyramid::LAYOUTS),
+            progress: format!("Set how progress is shown on stderr, from:\n{}", progress::PROGRESSSTYLES),
+            colors: format!("Set the color scheme from:\n{}", color::COLORLIST),
+            modes: format!("Set the coloring mode from:\n{}", color::COLORMODES),
+            alpha: format!("Add an alpha channel, making pixels transparent by one of:\n{}", color::ALPHAMODES),
+            patterns: format!("Set where supersamples are placed, from:\n{}", antialias::PATTERNS),
+            filters: format!("Set how supersamples are weighted, from:\n{}", antialias::FILTERS),
+            depths: format!("Set the bits per channel of the image from:\n{}", output::DEPTHS),
+            formats: format!("Set the image format instead of guessing it from the file name, from:\n{}", output::FORMATS),
+            videos: format!("Set the kind of video written when OUTPUT has no frame number in it,\ninstead of guessing it from the file name, from:\n{}", animate::VIDEOFORMATS),
+            seed_paths: format!("Set the path the seed follows, as one of:\n{}Ex: circle=-0.75,0,0.1    (round -0.75 + 0i at a distance of 0.1)", animate::SEEDPATHS),
+            interpolations: format!("Set how values get from one keyframe to the next, instead of as the file says, from:\n{}", animate::INTERPOLATIONS),
+            preview_formats: format!("Set how the preview is drawn, from:\n{}", preview::PREVIEWFORMATS),
+            explore_keys: format!("The fractal, iterations, coloring and output options are for starting without\na SCENE. Full size renders go to the first free file.\n\nKeys:\n{}\n\
+                                   Full example:\nmandelbrot explore --iterations=500 --color=magma", explore::EXPLORE_KEYS),
+            exit_codes: format!("Full example:\nmandelbrot julia --color=vaportest --seed=-0.4,0.6 -- julia.png 5000x5000 -2,2 2,-2\n\n\
+                                 Exit status:\n{}", error::EXITCODES),
         }
-        println!("NEW UPPERLEFT\t\t{}", upper_left);
-        println!("NEW LOWERRIGHT\t   {}", lower_right);
-    }
-    // 300x400 : (-2, 2), (1, -1)
-    // ratio = 300 / 400 = 0.75
-    // plane_ratio = 3 / 3 = 1.0
-    // cbound_y = 2 - (-1) = 3;
-    // cur_x = 1 - (-2) = 3;
-    // cbound_x = ratio * cbound_y = 0.75 * 3 = 2.25
-
-    let colors: [color::Color; 2048];
-    if let Some(color) = matches.value_of("COLORSCHEME") {
-        colors = color::colors(color).unwrap();
+    }
+}
+
+/// The options read by `fractal_args`, for subcommands that can render
+/// any fractal.
+fn fractal_opts<'a>() -> Vec<Arg<'a>> {
+    vec![Arg::new("SEED")
+             .short('s')
+             .long("seed")
+             .about("Render the Julia set for this seed instead of the Mandelbrot set\nEx: -0.4,0.6    (-0.4 + 0.6i)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("ALTFN")
+             .short('a')
+             .long("altfn")
+             .about("Render the burning ship fractal instead of the Mandelbrot set")
+             .conflicts_with("SEED")
+             .required(false)]
+}
+
+fn fractal_args(matches: &ArgMatches) -> Result<Fractal> {
+    Ok(if let Some(seed) = matches.value_of("SEED") {
+        Fractal::Julia(parse_point(seed)?)
+    } else if matches.occurrences_of("ALTFN") > 0 {
+        Fractal::BurningShip
     } else {
-        colors = color::colors("wikipedia").unwrap();
+        Fractal::Mandelbrot
+    })
+}
+
+/// The options shared by the `animate` subcommands.
+fn animation_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("FRAMES")
+             .short('n')
+             .long("frames")
+             .about("Set the number of frames (default 300, or up to the last keyframe)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("FPS")
+             .long("fps")
+             .about("Set the frames per second of the video (default 30)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("VIDEO")
+             .long("video")
+             .about(&help.videos)
+             .takes_value(true)
+             .required(false),
+         Arg::new("LOOPS")
+             .long("loops")
+             .about("Set how many times a gif or animated png plays, or 0 to loop forever (default 0)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("DITHER")
+             .long("dither")
+             .about("Dither gif frames, which hides banding from their 256 color palettes")
+             .required(false),
+         Arg::new("BLUR")
+             .long("motion-blur")
+             .about("Blur motion by averaging this many moments of every frame, each rendered in full\nEx: 8")
+             .takes_value(true)
+             .required(false),
+         Arg::new("SHUTTER")
+             .long("shutter")
+             .about("Set how long the shutter is open for motion blur, in degrees of the 360 a frame\nlasts (default 180)")
+             .takes_value(true)
+             .requires("BLUR")
+             .required(false)]
+}
+
+/// The options read by `scheduler_args`.
+fn scheduler_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("TILESIZE")
+             .long("tile-size")
+             .about("Set the width and height of the tiles the image is rendered in (default 64)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("ORDER")
+             .long("order")
+             .about(&help.orders)
+             .takes_value(true)
+             .required(false),
+         accel_opt(help),
+         iterations_opt()]
+}
+
+fn accel_opt(help: &HelpText) -> Arg<'_> {
+    Arg::new("ACCEL")
+        .long("accel")
+        .about(&help.accels)
+        .takes_value(true)
+        .required(false)
+}
+
+fn iterations_opt<'a>() -> Arg<'a> {
+    Arg::new("ITERATIONS")
+        .short('i')
+        .long("iterations")
+        .about("Set how many iterations a point gets before it is taken to be in the set (default 255)")
+        .takes_value(true)
+        .required(false)
+}
+
+/// The options read by `color_args`.
+fn coloring_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("COLORSCHEME")
+             .short('c')
+             .long("color")
+             .about(&help.colors)
+             .takes_value(true)
+             .required(false),
+         Arg::new("MODE")
+             .short('m')
+             .long("mode")
+             .about(&help.modes)
+             .takes_value(true)
+             .required(false),
+         Arg::new("RESOLUTION")
+             .short('r')
+             .long("resolution")
+             .about("Set the number of entries in the palette lookup table, or 0 to\nevaluate the palette exactly for every pixel (default 2048)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("OFFSET")
+             .long("palette-offset")
+             .about("Move the colors this far around the palette, where 1 is all the way round (default 0)\nEx: 0.25")
+             .takes_value(true)
+             .required(false),
+         Arg::new("ALPHA")
+             .long("alpha")
+             .about(&help.alpha)
+             .takes_value(true)
+             .required(false)]
+}
+
+/// The options read by `antialias_args`.
+fn antialias_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("SAMPLES")
+             .long("samples")
+             .about("Supersample pixels along edges with this many samples each\nEx: 16")
+             .takes_value(true)
+             .required(false),
+         Arg::new("PATTERN")
+             .long("pattern")
+             .about(&help.patterns)
+             .takes_value(true)
+             .required(false),
+         Arg::new("FILTER")
+             .long("filter")
+             .about(&help.filters)
+             .takes_value(true)
+             .required(false),
+         Arg::new("THRESHOLD")
+             .long("aa-threshold")
+             .about("Only supersample pixels that differ from a neighbour by more than this,\nor 0 to supersample every pixel (default 0.05)")
+             .takes_value(true)
+             .required(false)]
+}
+
+/// The options read by `output_args`.
+fn output_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("DEPTH")
+             .short('d')
+             .long("depth")
+             .about(&help.depths)
+             .takes_value(true)
+             .required(false),
+         Arg::new("FORMAT")
+             .long("format")
+             .about(&help.formats)
+             .takes_value(true)
+             .required(false),
+         Arg::new("QUALITY")
+             .short('q')
+             .long("quality")
+             .about("Set the JPEG quality from 1 to 100 (default 90); WebP is written\nlossless only and takes no quality")
+             .takes_value(true)
+             .required(false)]
+}
+
+/// The options read by `stream_args`.
+fn stream_opts<'a>() -> Vec<Arg<'a>> {
+    vec![Arg::new("STREAM")
+             .long("stream")
+             .about("Write the image a strip at a time as it renders, for images too big to\nhold in memory (png and tiff only)")
+             .conflicts_with("FIELD")
+             .required(false),
+         Arg::new("INFLIGHT")
+             .long("in-flight")
+             .about("Set how many rendered strips may wait to be written when streaming (default 4)")
+             .takes_value(true)
+             .requires("STREAM")
+             .required(false)]
+}
+
+fn stream_args(matches: &ArgMatches) -> Result<Option<usize>> {
+    if matches.occurrences_of("STREAM") == 0 {
+        return Ok(None)
     }
+    Ok(Some(parse_arg(matches, "INFLIGHT", "--in-flight")?.unwrap_or(stream::DEFAULT_IN_FLIGHT)))
+}
 
-    (file, bounds, upper_left, lower_right, colors)
+/// The options read by `checkpoint_args`.
+fn checkpoint_opts<'a>() -> Vec<Arg<'a>> {
+    vec![Arg::new("CHECKPOINT")
+             .long("checkpoint")
+             .about("Save finished tiles beside the image as they render, in <FILE>.mbcp, so\nthe render can be resumed if it is stopped")
+             .conflicts_with("STREAM")
+             .required(false),
+         Arg::new("INTERVAL")
+             .long("checkpoint-interval")
+             .about("Set how many seconds may pass between syncing the checkpoint to disk (default 30)")
+             .takes_value(true)
+             .required(false),
+         Arg::new("RESUME")
+             .long("resume")
+             .about("Pick up a render from its checkpoint, which must have been made with\nthe same fractal, size, region, iteration limit, tile size and acceleration")
+             .conflicts_with("STREAM")
+             .required(false)]
 }
 
-pub fn create_julia(file: &str,
-                    bounds: (usize, usize), 
-                    upper_left: Complex<f64>, 
-                    lower_right: Complex<f64>,
-                    seed: Complex<f64>,
-                    colors: [color::Color; 2048]) {
-    
-    let mut pixels = vec![0; (bounds.0 * 3) * bounds.1]; // * 3 for rgb
+fn checkpoint_args(matches: &ArgMatches) -> Result<Option<Duration>> {
+    if matches.occurrences_of("CHECKPOINT") == 0 && matches.occurrences_of("RESUME") == 0 {
+        return Ok(None)
+    }
+    let interval = parse_arg::<f64>(matches, "INTERVAL", "--checkpoint-interval")?;
+    match interval.map(Duration::try_from_secs_f64) {
+        None => Ok(Some(checkpoint::DEFAULT_INTERVAL)),
+        Some(Ok(interval)) => Ok(Some(interval)),
+        Some(Err(e)) => Err(Error::BadOption {
+            option: "--checkpoint-interval",
+            value: matches.value_of("INTERVAL").unwrap().to_string(),
+            reason: e.to_string(),
+        }),
+    }
+}
 
-    let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(bounds.0 * 3).enumerate().collect();
-    bands.into_par_iter().for_each(|(i, band)| {
-        let top = i;
-        let band_bounds = (bounds.0, 1);
-        let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-        let band_lower_right = pixel_to_point(bounds, (bounds.0, top + 1), upper_left, lower_right);
-        render_julia(band, band_bounds, band_upper_left, band_lower_right, seed, &colors);
-    });
-    write_image(&pixels, bounds, file).expect("error writing PNG file");
-}
-
-
-pub fn create_mandel(file: &str,
-                    bounds: (usize, usize), 
-                    upper_left: Complex<f64>, 
-                    lower_right: Complex<f64>,
-                    colors: [color::Color; 2048],
-                    altfn: bool) {
-    
-    let mut pixels = vec![0; (bounds.0 * 3) * bounds.1]; // * 3 for rgb
-
-    let bands: Vec<(usize, &mut [u8])> = pixels.chunks_mut(bounds.0 * 3).enumerate().collect();
-    bands.into_par_iter().for_each(|(i, band)| {
-        let top = i;
-        let band_bounds = (bounds.0, 1);
-        let band_upper_left = pixel_to_point(bounds, (0, top), upper_left, lower_right);
-        let band_lower_right = pixel_to_point(bounds, (bounds.0, top + 1), upper_left, lower_right);
-        if altfn {
-            render_burningship(band, band_bounds, band_upper_left, band_lower_right, &colors);
-        } else {
-            render_mandel(band, band_bounds, band_upper_left, band_lower_right, &colors);
+/// The options read by `progress_args`, and `--save-partial`.
+fn progress_opts(help: &HelpText) -> Vec<Arg<'_>> {
+    vec![Arg::new("PROGRESS")
+             .long("progress")
+             .about(&help.progress)
+             .takes_value(true)
+             .required(false),
+         Arg::new("PARTIAL")
+             .long("save-partial")
+             .about("If the render is stopped with Ctrl-C, write the finished part of the image,\nleaving the rest blank")
+             .required(false)]
+}
+
+fn progress_args(matches: &ArgMatches) -> Result<ProgressStyle> {
+    Ok(parse_arg(matches, "PROGRESS", "--progress")?.unwrap_or_default())
+}
+
+/// The options read by `preview_args`, with `--preview` itself for
+/// subcommands that otherwise render an image.
+fn preview_opts(help: &HelpText, flag: bool) -> Vec<Arg<'_>> {
+    let mut opts = vec![Arg::new("PREVIEWFORMAT")
+                            .long("preview-format")
+                            .about(&help.preview_formats)
+                            .takes_value(true)
+                            .required(false),
+                        Arg::new("COLUMNS")
+                            .long("columns")
+                            .about("Set how many columns wide the preview is (default $COLUMNS, or 80)")
+                            .takes_value(true)
+                            .required(false)];
+    if flag {
+        opts.insert(0, Arg::new("PREVIEW")
+                           .long("preview")
+                           .about("Draw a small version of the image in the terminal instead of rendering it")
+                           .required(false));
+    }
+    opts
+}
+
+fn preview_args(matches: &ArgMatches) -> Result<Preview> {
+    let format = match matches.value_of("PREVIEWFORMAT") {
+        None | Some("auto") => PreviewFormat::detect(),
+        Some(_) => parse_arg(matches, "PREVIEWFORMAT", "--preview-format")?.unwrap(),
+    };
+    let columns = match parse_arg(matches, "COLUMNS", "--columns")? {
+        Some(0) => return Err(Error::BadOption { option: "--columns", value: "0".to_string(), reason: "it has to be above zero".to_string() }),
+        Some(columns) => columns,
+        None => std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).filter(|&columns| columns > 0)
+            .unwrap_or(preview::DEFAULT_COLUMNS),
+    };
+    Ok(Preview { format, columns })
+}
+
+fn output_args(matches: &ArgMatches) -> Result<OutputOptions> {
+    Ok(OutputOptions {
+        format: parse_arg(matches, "FORMAT", "--format")?,
+        depth: parse_arg(matches, "DEPTH", "--depth")?,
+        quality: parse_arg(matches, "QUALITY", "--quality")?,
+        text: Vec::new(),
+    })
+}
+
+fn scheduler_args(matches: &ArgMatches) -> Result<Scheduler> {
+    let mut scheduler = Scheduler::default();
+    if let Some(size) = parse_arg(matches, "TILESIZE", "--tile-size")? {
+        scheduler.tile_size = size;
+    }
+    if let Some(order) = parse_arg(matches, "ORDER", "--order")? {
+        scheduler.order = order;
+    }
+    if let Some(accel) = parse_arg(matches, "ACCEL", "--accel")? {
+        scheduler.accel = accel;
+    }
+    if let Some(limit) = parse_arg(matches, "ITERATIONS", "--iterations")? {
+        scheduler.limit = limit;
+    }
+    Ok(scheduler)
+}
+
+fn antialias_args(matches: &ArgMatches) -> Result<Option<Antialias>> {
+    let samples = match parse_arg(matches, "SAMPLES", "--samples")? {
+        Some(0) => return Err(Error::BadOption { option: "--samples", value: "0".to_string(), reason: "there has to be at least one sample".to_string() }),
+        Some(samples) => samples,
+        None => return Ok(None),
+    };
+    Ok(Some(Antialias {
+        samples,
+        pattern: parse_arg(matches, "PATTERN", "--pattern")?.unwrap_or_default(),
+        filter: parse_arg(matches, "FILTER", "--filter")?.unwrap_or_default(),
+        threshold: parse_arg(matches, "THRESHOLD", "--aa-threshold")?.unwrap_or(antialias::DEFAULT_THRESHOLD),
+    }))
+}
+
+fn color_args(matches: &ArgMatches) -> Result<ColorSpec> {
+    Ok(ColorSpec {
+        palette: matches.value_of("COLORSCHEME").unwrap_or("wikipedia").to_string(),
+        resolution: parse_arg(matches, "RESOLUTION", "--resolution")?.unwrap_or(color::DEFAULT_RESOLUTION),
+        mode: parse_arg(matches, "MODE", "--mode")?.unwrap_or(ColorMode::Count),
+        alpha: parse_arg(matches, "ALPHA", "--alpha")?,
+        offset: parse_arg(matches, "OFFSET", "--palette-offset")?.unwrap_or(0.0),
+    })
+}
+
+/// Render the image in `args`, streaming it or keeping a checkpoint if asked
+/// to, and write it.
+fn render(args: &CommonArgs) -> Result<()> {
+    if let Some(preview) = args.preview {
+        preview.print(&mut io::stdout().lock(), &args.request)
+    } else if let Some(in_flight) = args.stream {
+        stream(in_flight, args)
+    } else if args.checkpoint.is_some() {
+        render_resumable(args)
+    } else {
+        let progress = tile_progress(args);
+        let mut finished = Vec::new();
+        let field = args.request.render_escape_with(|tile, samples| {
+            progress.record(samples);
+            finished.push(*tile);
+        })?;
+        conclude(&field, args, &progress, &finished)
+    }
+}
+
+/// Save the escape field if it was asked for, then color it, supersample its
+/// edges and write the image.
+fn finish(field: &EscapeField, args: &CommonArgs) -> Result<()> {
+    if let Some(field_file) = &args.field {
+        field.write(field_file).map_err(|e| Error::from(e).in_file(field_file))?;
+    }
+    let pixels = args.request.colorize(field);
+    write_image(&pixels, field.bounds, &args.file, &args.output).map_err(|e| Error::from(e).in_file(&args.file))
+}
+
+/// Report the end of a render, then write the image; or if it was cancelled,
+/// write what there is of it if that was asked for and exit.
+fn conclude(field: &EscapeField, args: &CommonArgs, progress: &Progress, finished: &[Tile]) -> Result<()> {
+    progress.finish();
+    if !progress::cancelled() {
+        return finish(field, args)
+    }
+    if args.save_partial {
+        save_partial(field, args, finished)?;
+        eprintln!("render cancelled, wrote the finished part to {}", args.file);
+    } else {
+        eprintln!("render cancelled");
+    }
+    std::process::exit(130);
+}
+
+/// Color just the `finished` tiles of `field` and write the image, with
+/// everything else left black, or transparent if there is an alpha channel.
+fn save_partial(field: &EscapeField, args: &CommonArgs, finished: &[Tile]) -> Result<()> {
+    let coloring = &args.request.coloring;
+    let bounds = field.bounds;
+    let mut pixels = color::colorize(field, coloring);
+    let mut blank = vec![true; bounds.0 * bounds.1];
+    for tile in finished {
+        for row in tile.y..tile.y + tile.height {
+            blank[row * bounds.0 + tile.x..row * bounds.0 + tile.x + tile.width].fill(false);
+        }
+    }
+    for (pixel, &blank) in pixels.chunks_mut(coloring.channels()).zip(blank.iter()) {
+        if blank {
+            pixel.fill(0.0);
+        }
+    }
+    write_image(&pixels, bounds, &args.file, &args.output).map_err(|e| Error::from(e).in_file(&args.file))
+}
+
+/// Progress for rendering every tile of the image in `args`.
+fn tile_progress(args: &CommonArgs) -> Progress {
+    let request = &args.request;
+    Progress::new(args.progress, "tiles", request.scheduler.tiles(request.bounds).len(),
+                  request.bounds.0 * request.bounds.1, request.scheduler.limit)
+}
+
+fn export_tiles(matches: &ArgMatches) -> Result<()> {
+    let upper_left = parse_point(matches.value_of("UPPERLEFT").unwrap())?;
+    let lower_right = parse_point(matches.value_of("LOWERRIGHT").unwrap())?;
+    check_viewport(upper_left, lower_right)?;
+    let coloring = color_args(matches)?.coloring()?;
+    let pyramid = Pyramid {
+        fractal: fractal_args(matches)?,
+        orbit: Orbit::default(),
+        upper_left,
+        lower_right,
+        rotation: 0.0,
+        layout: parse_arg(matches, "LAYOUT", "--layout")?.unwrap_or(pyramid::Layout::Xyz),
+        tile_size: parse_arg(matches, "SIZE", "--size")?.unwrap_or(pyramid::DEFAULT_TILE_SIZE),
+        max_zoom: parse_arg(matches, "MAXZOOM", "--max-zoom")?.unwrap_or(pyramid::DEFAULT_MAX_ZOOM),
+        scheduler: scheduler_args(matches)?,
+        coloring: &coloring,
+    };
+    pyramid.check()?;
+    let output = matches.value_of("OUTPUT").unwrap();
+    let summary = pyramid.export(std::path::Path::new(output), progress_args(matches)?)
+        .map_err(|e| Error::from(e).in_file(output))?;
+    println!("wrote {} tiles, skipped {} already there", summary.written, summary.skipped);
+    if progress::cancelled() {
+        eprintln!("export cancelled, run the same command again to finish it");
+        std::process::exit(130);
+    }
+    Ok(())
+}
+
+fn expmap(matches: &ArgMatches) -> Result<()> {
+    let file = matches.value_of("FILE").unwrap();
+    let width = match parse_arg(matches, "WIDTH", "WIDTH")?.unwrap() {
+        0 => return Err(Error::BadOption { option: "WIDTH", value: "0".to_string(), reason: "it has to be above zero".to_string() }),
+        width => width,
+    };
+    let center = parse_point(matches.value_of("CENTER").unwrap())?;
+    let radius = parse_arg::<f64>(matches, "RADIUS", "RADIUS")?.unwrap();
+    if !(radius.is_finite() && radius > 0.0) {
+        return Err(Error::BadOption { option: "RADIUS", value: radius.to_string(), reason: "it has to be above zero".to_string() })
+    }
+    let depth = parse_arg::<f64>(matches, "ZOOM", "ZOOM")?.unwrap();
+    if !(depth.is_finite() && depth > 1.0) {
+        return Err(Error::BadOption { option: "ZOOM", value: depth.to_string(), reason: "it has to be above 1".to_string() })
+    }
+    let limit = parse_arg(matches, "ITERATIONS", "--iterations")?.unwrap_or(LIMIT);
+    let coloring = color_args(matches)?.coloring()?;
+    let options = output_args(matches)?;
+
+    let mut map = ExpMap::new(fractal_args(matches)?, limit, center, width, radius, depth);
+    let (width, height) = map.field.bounds;
+    let progress = Progress::new(progress_args(matches)?, "rows", height, width * height, limit);
+    map.render(|samples| progress.record(samples));
+    progress.finish();
+    if progress::cancelled() {
+        eprintln!("render cancelled");
+        std::process::exit(130);
+    }
+    map.write(file).map_err(|e| Error::from(e).in_file(file))?;
+    if let Some(image) = matches.value_of("IMAGE") {
+        let pixels = color::colorize(&map.preview(), &coloring);
+        write_image(&pixels, map.field.bounds, image, &options).map_err(|e| Error::from(e).in_file(image))?;
+    }
+    Ok(())
+}
+
+/// The region a new exploration starts from, `DEFAULT_EXPLORE_WIDTH` across
+/// and as tall as the image.
+fn explore_region(fractal: Fractal, bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>) {
+    let center = match fractal {
+        Fractal::Julia(_) => Complex { re: 0.0, im: 0.0 },
+        _ => Complex { re: -0.5, im: 0.0 },
+    };
+    let height = DEFAULT_EXPLORE_WIDTH * bounds.1 as f64 / bounds.0 as f64;
+    let half = Complex { re: DEFAULT_EXPLORE_WIDTH / 2.0, im: -height / 2.0 };
+    (center - half, center + half)
+}
+
+fn explore(matches: &ArgMatches) -> Result<()> {
+    let pattern = matches.value_of("OUTPUT").unwrap_or("explore-%d.png");
+    if frame_filename(pattern, 0).is_none() {
+        return Err(Error::BadOption {
+            option: "--output",
+            value: pattern.to_string(),
+            reason: "it needs a %d to number the renders".to_string(),
+        })
+    }
+    let size = matches.value_of("SIZE").map(parse_bounds).transpose()?;
+    let mut scene = match matches.value_of("SCENE") {
+        Some(path) if path.to_ascii_lowercase().ends_with(".png") => Scene::from_image(path)?,
+        Some(path) => Scene::read(path)?,
+        None => {
+            let fractal = fractal_args(matches)?;
+            let bounds = size.unwrap_or(DEFAULT_EXPLORE_SIZE);
+            let (upper_left, lower_right) = explore_region(fractal, bounds);
+            Scene {
+                fractal: FractalSpec::new(fractal, Orbit::default()),
+                view: View::new(bounds, upper_left, lower_right),
+                render: scheduler_args(matches)?,
+                color: color_args(matches)?,
+                antialias: None,
+                output: OutputSpec::new(&frame_filename(pattern, 1).unwrap(), None, &output_args(matches)?),
+            }
         }
+    };
+    if let Some(size) = size {
+        scene.view.size = size.into();
+    }
+    let mut request = scene.to_request()?;
+    fit_region(&mut request);
+    scene.view = View::of(&request);
+    scene.output.field = None;
+
+    let bookmarks = matches.value_of("BOOKMARKS").unwrap_or("bookmarks.toml");
+    let explorer = Explorer::new(scene, (preview::DEFAULT_COLUMNS, 24), Bookmarks::read(bookmarks)?);
+    explore::run(explorer, bookmarks, |scene| {
+        let file = (1..).filter_map(|index| frame_filename(pattern, index))
+                        .find(|file| !std::path::Path::new(file).exists())
+                        .unwrap();
+        let mut scene = scene.clone();
+        scene.output.file = file.clone();
+        let request = scene.to_request()?;
+        let pixels = request.colorize(&request.render_escape()?);
+        let mut options = scene.output.options();
+        options.text = scene.png_text(&format!("mandelbrot {}", VERSION));
+        write_image(&pixels, request.bounds, &file, &options).map_err(|e| Error::from(e).in_file(&file))?;
+        Ok(format!("wrote {}", file))
+    })
+}
+
+/// Render while keeping a checkpoint beside the image file, starting from it
+/// if resuming, and remove it once the image is written.
+fn render_resumable(args: &CommonArgs) -> Result<()> {
+    let request = &args.request;
+    let interval = args.checkpoint.unwrap();
+    let path = checkpoint::sidecar(&args.file);
+    let params = RenderParams {
+        fractal: request.fractal,
+        bounds: request.bounds,
+        upper_left: request.upper_left,
+        lower_right: request.lower_right,
+        limit: request.scheduler.limit,
+        tile_size: request.scheduler.tile_size,
+        accel: request.scheduler.accel,
+        power: request.orbit.power,
+        bailout: request.orbit.bailout,
+        rotation: request.rotation,
+    };
+    let mut field = request.field();
+    let (mut checkpoint, done) = if args.resume {
+        Checkpoint::resume(&path, &params, &mut field, interval)
+    } else {
+        Checkpoint::create(&path, &params, interval).map(|checkpoint| (checkpoint, Vec::new()))
+    }.map_err(|e| Error::from(e).in_file(&path))?;
+    let skip: HashSet<(usize, usize)> = done.iter().map(|tile| (tile.x, tile.y)).collect();
+    let tiles: Vec<_> = request.scheduler.tiles(request.bounds).into_iter().filter(|tile| !skip.contains(&(tile.x, tile.y))).collect();
+    let progress = Progress::new(args.progress, "tiles", tiles.len(), tiles.iter().map(Tile::len).sum(), request.scheduler.limit);
+    let mut finished = done;
+    // the render can't be stopped from here, so the first failure is kept
+    // for when it is over
+    let mut failed = None;
+    request.scheduler.render_tiles(&mut field, request.fractal, request.orbit, request.rotation, &tiles, |tile, samples| {
+        if failed.is_none() {
+            failed = checkpoint.record(tile, samples).err();
+        }
+        progress.record(samples);
+        finished.push(*tile);
     });
-    write_image(&pixels, bounds, file).expect("error writing PNG file");
+    drop(checkpoint);
+    if let Some(e) = failed {
+        return Err(Error::from(e).in_file(&path))
+    }
+    if progress::cancelled() {
+        eprintln!("the checkpoint is in {}, finish the render with --resume", path);
+    }
+    conclude(&field, args, &progress, &finished)?;
+    std::fs::remove_file(&path).map_err(|e| Error::from(e).in_file(&path))
+}
+
+/// Render straight to the image file, a strip at a time.
+fn stream(in_flight: usize, args: &CommonArgs) -> Result<()> {
+    let request = &args.request;
+    let strip_rows = request.scheduler.tile_size.max(1);
+    let render = StreamRender {
+        fractal: request.fractal,
+        orbit: request.orbit,
+        bounds: request.bounds,
+        upper_left: request.upper_left,
+        lower_right: request.lower_right,
+        rotation: request.rotation,
+        scheduler: &request.scheduler,
+        coloring: &request.coloring,
+        antialias: request.antialias.as_ref(),
+        in_flight,
+        progress: &Progress::new(args.progress, "strips", request.bounds.1.div_ceil(strip_rows),
+                                 request.bounds.0 * request.bounds.1, request.scheduler.limit),
+        save_partial: args.save_partial,
+    };
+    let result = render.write(&args.file, &args.output).map_err(|e| Error::from(e).in_file(&args.file));
+    render.progress.finish();
+    if !progress::cancelled() {
+        return result
+    }
+    if args.save_partial {
+        result?;
+        eprintln!("render cancelled, wrote the finished part to {}", args.file);
+    } else {
+        // the file stops partway, so it is no use to anyone
+        let _ = std::fs::remove_file(&args.file);
+        eprintln!("render cancelled");
+    }
+    std::process::exit(130);
+}
+
+/// The frames that `animate` subcommands render when not told otherwise.
+const DEFAULT_FRAMES: usize = 300;
+const DEFAULT_FPS: u32 = 30;
+const DEFAULT_SHUTTER: f64 = 180.0;
+const DEFAULT_EXPLORE_SIZE: (usize, usize) = (1920, 1080);
+const DEFAULT_EXPLORE_WIDTH: f64 = 4.0;
+
+fn animate_zoom(matches: &ArgMatches) -> Result<()> {
+    let output = matches.value_of("OUTPUT").unwrap();
+    let (mut scene, start, options) = animation_scene(matches, output)?;
+    let depth = parse_arg::<f64>(matches, "ZOOM", "ZOOM")?.unwrap();
+    if !(depth.is_finite() && depth > 0.0) {
+        return Err(Error::BadOption { option: "ZOOM", value: depth.to_string(), reason: "it has to be above zero".to_string() })
+    }
+    let zoom = Zoom {
+        start,
+        target: parse_point(matches.value_of("TARGET").unwrap())?,
+        depth,
+        frames: frames_args(matches)?,
+        iteration_growth: parse_arg(matches, "GROWTH", "--grow-iterations")?.unwrap_or(0.0),
+    };
+    write_animation(matches, output, &zoom, &mut scene, &options)
+}
+
+fn animate_cycle(matches: &ArgMatches) -> Result<()> {
+    let output = matches.value_of("OUTPUT").unwrap();
+    let (mut scene, request, options) = animation_scene(matches, output)?;
+    let cycles = parse_arg::<f64>(matches, "CYCLES", "--cycles")?.unwrap_or(1.0);
+    if !cycles.is_finite() {
+        return Err(Error::BadOption { option: "--cycles", value: matches.value_of("CYCLES").unwrap().to_string(), reason: "it has to be a number".to_string() })
+    }
+    let frames = frames_args(matches)?;
+
+    // the only render there is
+    let progress = Progress::new(progress_args(matches)?, "tiles", request.scheduler.tiles(request.bounds).len(),
+                                 request.bounds.0 * request.bounds.1, request.scheduler.limit);
+    let field = request.render_escape_with(|_, samples| progress.record(samples))?;
+    progress.finish();
+    if progress::cancelled() {
+        eprintln!("animation cancelled");
+        std::process::exit(130);
+    }
+    let cycle = Cycle { request, field, frames, cycles };
+    write_animation(matches, output, &cycle, &mut scene, &options)
+}
+
+fn animate_julia(matches: &ArgMatches) -> Result<()> {
+    let output = matches.value_of("OUTPUT").unwrap();
+    let path: SeedPath = parse_arg(matches, "PATH", "PATH")?.unwrap();
+    let (mut scene, mut start, options) = animation_scene(matches, output)?;
+    start.fractal = Fractal::Julia(path.at(0.0));
+    scene.fractal = FractalSpec::new(start.fractal, start.orbit);
+    let inset = if matches.is_present("INSET") {
+        Some(Inset::new((start.bounds.0 / 4).max(8), &path, &start)?)
+    } else {
+        None
+    };
+    let animation = SeedAnimation { start, path, frames: frames_args(matches)?, inset };
+    write_animation(matches, output, &animation, &mut scene, &options)
+}
+
+fn animate_reproject(matches: &ArgMatches) -> Result<()> {
+    let (strip, output) = (matches.value_of("STRIP").unwrap(), matches.value_of("OUTPUT").unwrap());
+    let map = ExpMap::read(strip).map_err(|e| Error::from(e).in_file(strip))?;
+    let bounds = parse_bounds(matches.value_of("PIXELS").unwrap())?;
+    let options = output_args(matches)?;
+    let color = color_args(matches)?;
+    let mut request = RenderRequest::new(map.fractal).size(bounds.0, bounds.1);
+    request.coloring = color.coloring()?;
+
+    let reprojection = Reprojection::new(map, request, frames_args(matches)?).map_err(|e| e.in_file(strip))?;
+    if reprojection.map.field.bounds.0 < reprojection.sharp_width() {
+        eprintln!("warning: the map is {} samples round, frames this size need {} to be sharp at their corners",
+                  reprojection.map.field.bounds.0, reprojection.sharp_width());
+    }
+    let start = &reprojection.start;
+    let mut scene = Scene {
+        fractal: FractalSpec::new(start.fractal, start.orbit),
+        view: View::new(bounds, start.upper_left, start.lower_right),
+        render: start.scheduler,
+        color,
+        antialias: None,
+        output: OutputSpec::new(output, None, &options),
+    };
+    write_animation(matches, output, &reprojection, &mut scene, &options)
+}
+
+/// The scene an animation given by the usual region and options starts
+/// from, with its request and output options.
+fn animation_scene(matches: &ArgMatches, output: &str) -> Result<(Scene, RenderRequest, OutputOptions)> {
+    let upper_left = parse_point(matches.value_of("UPPERLEFT").unwrap())?;
+    let lower_right = parse_point(matches.value_of("LOWERRIGHT").unwrap())?;
+    check_viewport(upper_left, lower_right)?;
+    let options = output_args(matches)?;
+    let scene = Scene {
+        fractal: FractalSpec::new(fractal_args(matches)?, Orbit::default()),
+        view: View::new(parse_bounds(matches.value_of("PIXELS").unwrap())?, upper_left, lower_right),
+        render: scheduler_args(matches)?,
+        color: color_args(matches)?,
+        antialias: antialias_args(matches)?,
+        output: OutputSpec::new(output, None, &options),
+    };
+    let mut request = scene.to_request()?;
+    // stdout may be taken by the video
+    if fit_region(&mut request) {
+        eprintln!("NEW UPPERLEFT\t\t{}", request.upper_left);
+        eprintln!("NEW LOWERRIGHT\t   {}", request.lower_right);
+    }
+    Ok((scene, request, options))
+}
+
+fn animate_keyframes(matches: &ArgMatches) -> Result<()> {
+    let output = matches.value_of("OUTPUT").unwrap();
+    let mut scene = Scene::read(matches.value_of("SCENE").unwrap())?;
+    scene.output.file = output.to_string();
+    scene.output.field = None;
+    let mut start = scene.to_request()?;
+    if fit_region(&mut start) {
+        eprintln!("NEW UPPERLEFT\t\t{}", start.upper_left);
+        eprintln!("NEW LOWERRIGHT\t   {}", start.lower_right);
+    }
+
+    let file = matches.value_of("KEYFRAMES").unwrap();
+    let mut keyframes = Keyframes::read(file)?;
+    if let Some(interpolation) = parse_arg::<Interpolation>(matches, "INTERPOLATION", "--interpolation")? {
+        keyframes.interpolation = interpolation;
+    }
+    let frames = if matches.is_present("FRAMES") { Some(frames_args(matches)?) } else { None };
+    let animation = keyframes.animate(start, frames).map_err(|e| e.in_file(file))?;
+    let options = scene.output.options();
+    write_animation(matches, output, &animation, &mut scene, &options)
+}
+
+/// Render the frames of `animation` and write them to `output` as numbered
+/// images or a video, blurring motion if asked to. `scene` is what each
+/// frame's scene is made from.
+fn write_animation<A: Animation>(matches: &ArgMatches, output: &str, animation: &A, scene: &mut Scene, options: &OutputOptions) -> Result<()> {
+    let frames = animation.frames();
+    let (mut limit, mut too_deep) = (0, false);
+    for index in 0..frames {
+        let request = animation.frame(index);
+        let center = (request.upper_left + request.lower_right) / 2.0;
+        let pixel_size = (request.lower_right.re - request.upper_left.re) / request.bounds.0 as f64;
+        limit = limit.max(request.scheduler.limit);
+        too_deep |= pixel_size < 4.0 * f64::EPSILON * center.norm().max(1.0);
+    }
+    if too_deep {
+        eprintln!("warning: some frames are zoomed in further than double precision can go, and will look blocky");
+    }
+
+    let blur = blur_args(matches)?;
+    let start = animation.frame(0);
+    let (bounds, pixels) = (start.bounds, start.bounds.0 * start.bounds.1);
+    let progress = Progress::new(progress_args(matches)?, "frames", frames, frames * pixels, limit);
+    let result = if frame_filename(output, 0).is_some() {
+        render_frames(animation, blur.as_ref(), |index, request, field, pixels| {
+            progress.record(&field.samples);
+            write_frame(output, index, request, &pixels, scene, options)
+        })
+    } else {
+        write_video(output, matches, bounds, frames, start.coloring.alpha.is_some(), |video| {
+            render_frames(animation, blur.as_ref(), |_, request, field, pixels| {
+                progress.record(&field.samples);
+                video.write_frame(&pixels, request.coloring.channels()).map_err(|e| Error::from(e).in_file(output))
+            })
+        })
+    };
+    progress.finish();
+    // an animated png that stops early can't be finished, which is no
+    // reason to complain
+    if progress::cancelled() {
+        eprintln!("animation cancelled");
+        std::process::exit(130);
+    }
+    result
+}
+
+fn blur_args(matches: &ArgMatches) -> Result<Option<MotionBlur>> {
+    let samples = match parse_arg(matches, "BLUR", "--motion-blur")? {
+        Some(0) => return Err(Error::BadOption { option: "--motion-blur", value: "0".to_string(), reason: "there has to be at least one moment".to_string() }),
+        Some(samples) => samples,
+        None => return Ok(None),
+    };
+    let shutter = parse_arg::<f64>(matches, "SHUTTER", "--shutter")?.unwrap_or(DEFAULT_SHUTTER);
+    if !(shutter > 0.0 && shutter <= 360.0) {
+        return Err(Error::BadOption { option: "--shutter", value: matches.value_of("SHUTTER").unwrap().to_string(),
+                                      reason: "it has to be above 0 and at most 360".to_string() })
+    }
+    Ok(Some(MotionBlur { samples, shutter }))
+}
+
+fn frames_args(matches: &ArgMatches) -> Result<usize> {
+    match parse_arg(matches, "FRAMES", "--frames")?.unwrap_or(DEFAULT_FRAMES) {
+        0 => Err(Error::BadOption { option: "--frames", value: "0".to_string(), reason: "there has to be at least one".to_string() }),
+        frames => Ok(frames),
+    }
+}
+
+/// Write frame `index` of an animation to its file in the sequence named by
+/// `pattern`, recording the scene it was rendered from in it.
+fn write_frame(pattern: &str, index: usize, request: &RenderRequest, pixels: &[f32], scene: &mut Scene, options: &OutputOptions) -> Result<()> {
+    let file = frame_filename(pattern, index).unwrap();
+    scene.fractal = FractalSpec::new(request.fractal, request.orbit);
+    scene.view = View::of(request);
+    scene.render = request.scheduler;
+    scene.color.offset = request.coloring.offset;
+    scene.output.file = file.clone();
+    let options = OutputOptions { text: scene.png_text(&format!("mandelbrot {}", VERSION)), ..options.clone() };
+    write_image(pixels, request.bounds, &file, &options).map_err(|e| Error::from(e).in_file(&file))
+}
+
+/// Open the video named by `output`, or stdout for `-`, for `render` to write
+/// its `frames` frames to, then finish it.
+fn write_video<F>(output: &str, matches: &ArgMatches, bounds: (usize, usize), frames: usize, alpha: bool, render: F) -> Result<()>
+    where F: FnOnce(&mut VideoWriter<&mut BufWriter<Box<dyn Write>>>) -> Result<()> {
+    let format = match parse_arg(matches, "VIDEO", "--video")? {
+        Some(format) => format,
+        None if output == "-" => VideoFormat::Y4m,
+        None => VideoFormat::from_filename(output).unwrap_or(VideoFormat::Y4m),
+    };
+    if alpha && !format.supports_alpha() {
+        let reason = "video has no alpha channel, write the frames as png images or a gif or apng instead".to_string();
+        return Err(match matches.value_of("ALPHA") {
+            Some(value) => Error::BadOption { option: "--alpha", value: value.to_string(), reason },
+            // the scene asked for it
+            None => Error::BadScene(reason),
+        })
+    }
+    let options = VideoOptions {
+        format,
+        fps: parse_arg(matches, "FPS", "--fps")?.unwrap_or(DEFAULT_FPS),
+        loops: parse_arg(matches, "LOOPS", "--loops")?.unwrap_or(0),
+        dither: matches.is_present("DITHER"),
+        frames,
+    };
+    if options.fps == 0 {
+        return Err(Error::BadOption { option: "--fps", value: "0".to_string(), reason: "it has to be above zero".to_string() })
+    }
+    let file: Box<dyn Write> = if output == "-" {
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(output).map_err(|e| Error::from(e).in_file(output))?)
+    };
+    if format == VideoFormat::Raw {
+        eprintln!("writing raw rgb24 video, {}x{} at {} fps", bounds.0, bounds.1, options.fps);
+    }
+    let mut out = BufWriter::new(file);
+    let mut video = VideoWriter::new(&mut out, bounds, &options).map_err(|e| Error::from(e).in_file(output))?;
+    render(&mut video)?;
+    video.finish().and_then(|_| out.flush()).map_err(|e| Error::from(e).in_file(output))
 }
@@ -1,8 +1,8 @@
-pub fn monotonic_cubic_preprocess(y: &[u8], knots: &[f64]) -> Vec<f64> {
+pub fn monotonic_cubic_preprocess(y: &[f64], knots: &[f64]) -> Vec<f64> {
     let n: usize = y.len();
     let mut slopes: Vec<f64> = Vec::new();
     for i in 0..n - 1 {
-        slopes.push((y[i+1] as f64 - y[i] as f64) / (knots[i+1] - knots[i]));
+        slopes.push((y[i+1] - y[i]) / (knots[i+1] - knots[i]));
     }
     let mut m: Vec<f64> = Vec::new();
     for k in 1..slopes.len() {
@@ -49,25 +49,24 @@ pub fn monotonic_cubic_preprocess(y: &[u8], knots: &[f64]) -> Vec<f64> {
         }
     }
     
-    return Vec::from(m)
+    m
 }
 
-pub fn interpolate(x: f64, knots: &[f64], y: &[u8], m: &Vec<f64>) -> u8 {
+/// Evaluate the spline through `y` at `knots`, with slopes `m`, at `x`. The
+/// knots must be increasing. Before the first knot the first piece carries
+/// on, so every `x` has a value.
+pub fn interpolate(x: f64, knots: &[f64], y: &[f64], m: &[f64]) -> f64 {
     let n: usize = knots.len();
     if x >= knots[n - 1] {
         let k = n - 2;
         let delta = knots[k+1] - knots[k];
         let t = (x - knots[k+1]) / delta;
-        return (y[k] as f64 * h00(t) + delta * m[k] * h10(t) + y[k+1] as f64 * h01(t) + delta * m[k+1] * h11(t)) as u8
+        return y[k] * h00(t) + delta * m[k] * h10(t) + y[k+1] * h01(t) + delta * m[k+1] * h11(t)
     }
-    for k in 0..n - 1 {
-        if knots[k] <= x && x <= knots[k+1] {
-            let delta = knots[k+1] - knots[k];
-            let t = (x - knots[k]) / delta;
-            return (y[k] as f64 * h00(t) + delta * m[k] * h10(t) + y[k+1] as f64 * h01(t) + delta * m[k+1] * h11(t)) as u8
-        }
-    }
-    panic!("should be logically impossible")
+    let k = (0..n - 1).find(|&k| knots[k] <= x && x <= knots[k+1]).unwrap_or(0);
+    let delta = knots[k+1] - knots[k];
+    let t = (x - knots[k]) / delta;
+    y[k] * h00(t) + delta * m[k] * h10(t) + y[k+1] * h01(t) + delta * m[k+1] * h11(t)
 }
 
 #[inline]
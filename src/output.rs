@@ -0,0 +1,408 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use image::{DynamicImage, ImageBuffer, ImageError, ImageResult};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::error::{DecodingError, EncodingError, ImageFormatHint, ParameterError, ParameterErrorKind};
+use serde::{Deserialize, Serialize};
+use tiff::encoder::{colortype, TiffEncoder, TiffValue};
+
+use crate::color::srgb_to_linear;
+
+/// The file formats `write_image` knows how to produce.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    Png,
+    #[serde(alias = "jpg")]
+    Jpeg,
+    WebP,
+    #[serde(alias = "tif")]
+    Tiff,
+    Bmp,
+    #[serde(rename = "ppm", alias = "pnm")]
+    Pnm,
+    Tga,
+    Exr,
+    Pfm,
+}
+
+pub const FORMATS: &str = "png\njpeg (jpg)\nwebp (lossless only)\ntiff (tif)\nbmp\nppm\ntga\nexr\npfm\n";
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "png" => Ok(ImageFormat::Png),
+            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+            "webp" => Ok(ImageFormat::WebP),
+            "tif" | "tiff" => Ok(ImageFormat::Tiff),
+            "bmp" => Ok(ImageFormat::Bmp),
+            "ppm" | "pnm" => Ok(ImageFormat::Pnm),
+            "tga" => Ok(ImageFormat::Tga),
+            "exr" => Ok(ImageFormat::Exr),
+            "pfm" => Ok(ImageFormat::Pfm),
+            _ => Err(format!("unknown image format {}, expected one of:\n{}", s, FORMATS)),
+        }
+    }
+}
+
+impl ImageFormat {
+    /// Guess the format from the extension of `filename`.
+    pub fn from_filename(filename: &str) -> Option<ImageFormat> {
+        Path::new(filename).extension()?.to_str()?.parse().ok()
+    }
+
+    /// The depth used when none is asked for.
+    fn default_depth(self) -> Depth {
+        match self {
+            ImageFormat::Exr | ImageFormat::Pfm => Depth::Float,
+            _ => Depth::Eight,
+        }
+    }
+
+    fn supports_alpha(self) -> bool {
+        !matches!(self, ImageFormat::Jpeg | ImageFormat::Pnm | ImageFormat::Pfm)
+    }
+
+    fn supports(self, depth: Depth) -> bool {
+        match self {
+            ImageFormat::Png | ImageFormat::Tiff => depth != Depth::Float,
+            ImageFormat::Exr | ImageFormat::Pfm => depth == Depth::Float,
+            _ => depth == Depth::Eight,
+        }
+    }
+}
+
+/// How `write_image` should encode its file. Anything left as `None` falls
+/// back to what the file extension and format suggest.
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    pub format: Option<ImageFormat>,
+    pub depth: Option<Depth>,
+    /// JPEG quality, from 1 to 100.
+    pub quality: Option<u8>,
+    /// Keyword and text pairs to store in PNG files. Other formats leave them
+    /// out.
+    pub text: Vec<(String, String)>,
+}
+
+/// The JPEG quality used when none is asked for.
+const DEFAULT_QUALITY: u8 = 90;
+
+/// Bits per channel in the output file.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Depth {
+    #[serde(rename = "8")]
+    Eight,
+    #[serde(rename = "16")]
+    Sixteen,
+    /// 32-bit floating point, holding linear light rather than sRGB values.
+    #[serde(rename = "32", alias = "float")]
+    Float,
+}
+
+pub const DEPTHS: &str = "8 (default, except for exr and pfm)\n16 (png and tiff only)\n32 (float, exr and pfm only)\n";
+
+impl FromStr for Depth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "8" => Ok(Depth::Eight),
+            "16" => Ok(Depth::Sixteen),
+            "32" | "float" => Ok(Depth::Float),
+            _ => Err(format!("unsupported bit depth {}", s)),
+        }
+    }
+}
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds`, to the
+/// file named `filename`.
+///
+/// `pixels` holds three sRGB channels per pixel, or four when the last one is
+/// alpha, each in `[0, 1]`. The format is picked from `options`, or else from
+/// the file extension, and unknown extensions are an error rather than a
+/// guess.
+pub fn write_image(pixels: &[f32], bounds: (usize, usize), filename: &str, options: &OutputOptions) -> ImageResult<()> {
+    let channels = pixels.len() / (bounds.0 * bounds.1);
+    assert!(pixels.len() == bounds.0 * bounds.1 * channels && (channels == 3 || channels == 4));
+    let (format, depth) = resolve(filename, channels, options)?;
+    match format {
+        ImageFormat::Pfm => return write_pfm(pixels, bounds, filename),
+        ImageFormat::Png => return write_png_strips(Some(pixels), bounds, channels, depth, filename, &options.text),
+        _ => (),
+    }
+
+    let image = to_image(pixels, bounds, channels, depth);
+    match format {
+        ImageFormat::Jpeg => {
+            let quality = options.quality.unwrap_or(DEFAULT_QUALITY);
+            if !(1..=100).contains(&quality) {
+                return Err(parameter_error(format!("JPEG quality must be from 1 to 100, not {}", quality)));
+            }
+            let output = BufWriter::new(File::create(filename)?);
+            JpegEncoder::new_with_quality(output, quality)
+                .encode(image.as_bytes(), image.width(), image.height(), image.color())?;
+        }
+        ImageFormat::WebP => {
+            // image's own WebP encoder has no lossy mode.
+            let output = BufWriter::new(File::create(filename)?);
+            WebPEncoder::new_lossless(output)
+                .encode(image.as_bytes(), image.width(), image.height(), image.color())?;
+        }
+        _ => image.save_with_format(filename, image_format(format))?,
+    }
+    Ok(())
+}
+
+/// Write an image that arrives a strip at a time, without ever holding all of
+/// it in memory.
+///
+/// `strips` yields the pixels of `strip_rows` rows at a time, top to bottom,
+/// laid out as for `write_image`; the last strip may be shorter. Only PNG and
+/// TIFF files can be written this way.
+pub fn write_image_strips<I>(strips: I,
+                             bounds: (usize, usize),
+                             channels: usize,
+                             strip_rows: usize,
+                             filename: &str,
+                             options: &OutputOptions) -> ImageResult<()>
+    where I: IntoIterator<Item = Vec<f32>> {
+    assert!(channels == 3 || channels == 4);
+    let (format, depth) = resolve(filename, channels, options)?;
+    match (format, depth, channels) {
+        (ImageFormat::Png, _, _) => write_png_strips(strips, bounds, channels, depth, filename, &options.text),
+        (ImageFormat::Tiff, Depth::Eight, 3) => write_tiff_strips::<colortype::RGB8, _>(strips, bounds, strip_rows, 255.0, filename),
+        (ImageFormat::Tiff, Depth::Eight, _) => write_tiff_strips::<colortype::RGBA8, _>(strips, bounds, strip_rows, 255.0, filename),
+        (ImageFormat::Tiff, _, 3) => write_tiff_strips::<colortype::RGB16, _>(strips, bounds, strip_rows, 65535.0, filename),
+        (ImageFormat::Tiff, _, _) => write_tiff_strips::<colortype::RGBA16, _>(strips, bounds, strip_rows, 65535.0, filename),
+        _ => Err(parameter_error(format!("{:?} files can't be streamed, only png and tiff", format))),
+    }
+}
+
+/// Work out the format and depth to write, and check that they can hold an
+/// image with `channels` channels.
+fn resolve(filename: &str, channels: usize, options: &OutputOptions) -> ImageResult<(ImageFormat, Depth)> {
+    let format = options.format
+        .or_else(|| ImageFormat::from_filename(filename))
+        .ok_or_else(|| parameter_error(format!("can't tell the image format of {}, use an extension from:\n{}", filename, FORMATS)))?;
+    let depth = options.depth.unwrap_or_else(|| format.default_depth());
+    if !format.supports(depth) {
+        return Err(parameter_error(format!("{:?} files can't be written at {:?} depth", format, depth)));
+    }
+    if channels == 4 && !format.supports_alpha() {
+        return Err(parameter_error(format!("{:?} files can't have an alpha channel", format)));
+    }
+    if options.quality.is_some() && format == ImageFormat::WebP {
+        return Err(parameter_error("WebP files are only written lossless, so they don't have a quality setting; use jpeg for lossy output".to_string()));
+    }
+    if options.quality.is_some() && format != ImageFormat::Jpeg {
+        return Err(parameter_error(format!("{:?} files don't have a quality setting", format)));
+    }
+    Ok((format, depth))
+}
+
+/// PNG rows are compressed as they arrive, 16-bit samples big-endian. Text
+/// goes in tEXt chunks if it is plain ASCII, and in iTXt chunks otherwise,
+/// all of them ahead of the image data.
+fn write_png_strips<I>(strips: I,
+                       bounds: (usize, usize),
+                       channels: usize,
+                       depth: Depth,
+                       filename: &str,
+                       text: &[(String, String)]) -> ImageResult<()>
+    where I: IntoIterator,
+          I::Item: AsRef<[f32]> {
+    let output = BufWriter::new(File::create(filename)?);
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set_color(if channels == 4 { png::ColorType::Rgba } else { png::ColorType::Rgb });
+    encoder.set_depth(if depth == Depth::Sixteen { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+    for (keyword, text) in text {
+        if keyword.is_ascii() && text.is_ascii() {
+            encoder.add_text_chunk(keyword.clone(), text.clone())
+        } else {
+            encoder.add_itxt_chunk(keyword.clone(), text.clone())
+        }.map_err(|e| encoding_error(image::ImageFormat::Png, e))?;
+    }
+    let mut writer = encoder.write_header()
+        .and_then(png::Writer::into_stream_writer)
+        .map_err(|e| encoding_error(image::ImageFormat::Png, e))?;
+    for strip in strips {
+        let strip = strip.as_ref();
+        if depth == Depth::Sixteen {
+            let bytes: Vec<u8> = quantize::<u16>(strip, 65535.0).iter().flat_map(|v| v.to_be_bytes()).collect();
+            writer.write_all(&bytes)?;
+        } else {
+            writer.write_all(&quantize::<u8>(strip, 255.0))?;
+        }
+    }
+    writer.finish().map_err(|e| encoding_error(image::ImageFormat::Png, e))
+}
+
+/// The text chunks of the PNG file `filename`, as keyword and text pairs.
+/// Only the chunks ahead of the image data are read, which is where
+/// `write_image` puts them.
+pub fn read_png_text(filename: &str) -> ImageResult<Vec<(String, String)>> {
+    let decoding_error = |e| ImageError::Decoding(DecodingError::new(ImageFormatHint::Exact(image::ImageFormat::Png), e));
+    let reader = png::Decoder::new(BufReader::new(File::open(filename)?)).read_info().map_err(decoding_error)?;
+    let info = reader.info();
+    let mut text: Vec<_> = info.uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.clone(), chunk.text.clone())).collect();
+    for chunk in &info.compressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.get_text().map_err(decoding_error)?));
+    }
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text().map_err(decoding_error)?));
+    }
+    Ok(text)
+}
+
+/// TIFF files are made of strips already, so each one is written as it is.
+fn write_tiff_strips<C, I>(strips: I, bounds: (usize, usize), strip_rows: usize, max: f32, filename: &str) -> ImageResult<()>
+    where C: colortype::ColorType,
+          C::Inner: num::NumCast + num::Zero,
+          [C::Inner]: TiffValue,
+          I: IntoIterator<Item = Vec<f32>> {
+    let tiff_error = |e| encoding_error(image::ImageFormat::Tiff, e);
+    let mut encoder = TiffEncoder::new(BufWriter::new(File::create(filename)?)).map_err(tiff_error)?;
+    let mut image = encoder.new_image::<C>(bounds.0 as u32, bounds.1 as u32).map_err(tiff_error)?;
+    image.rows_per_strip(strip_rows as u32).map_err(tiff_error)?;
+    for strip in strips {
+        image.write_strip(&quantize::<C::Inner>(&strip, max)).map_err(tiff_error)?;
+    }
+    image.finish().map_err(tiff_error)
+}
+
+/// Quantize `pixels` to `depth`. Float images hold linear light, with the
+/// color premultiplied by alpha as OpenEXR expects.
+pub fn to_image(pixels: &[f32], bounds: (usize, usize), channels: usize, depth: Depth) -> DynamicImage {
+    let (width, height) = (bounds.0 as u32, bounds.1 as u32);
+    match (depth, channels) {
+        (Depth::Eight, 3) => DynamicImage::ImageRgb8(ImageBuffer::from_raw(width, height, quantize(pixels, 255.0)).unwrap()),
+        (Depth::Eight, _) => DynamicImage::ImageRgba8(ImageBuffer::from_raw(width, height, quantize(pixels, 255.0)).unwrap()),
+        (Depth::Sixteen, 3) => DynamicImage::ImageRgb16(ImageBuffer::from_raw(width, height, quantize(pixels, 65535.0)).unwrap()),
+        (Depth::Sixteen, _) => DynamicImage::ImageRgba16(ImageBuffer::from_raw(width, height, quantize(pixels, 65535.0)).unwrap()),
+        (Depth::Float, 3) => DynamicImage::ImageRgb32F(ImageBuffer::from_raw(width, height, linearize(pixels, 3)).unwrap()),
+        (Depth::Float, _) => DynamicImage::ImageRgba32F(ImageBuffer::from_raw(width, height, linearize(pixels, 4)).unwrap()),
+    }
+}
+
+fn image_format(format: ImageFormat) -> image::ImageFormat {
+    match format {
+        ImageFormat::Png => image::ImageFormat::Png,
+        ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+        ImageFormat::WebP => image::ImageFormat::WebP,
+        ImageFormat::Tiff => image::ImageFormat::Tiff,
+        ImageFormat::Bmp => image::ImageFormat::Bmp,
+        ImageFormat::Pnm => image::ImageFormat::Pnm,
+        ImageFormat::Tga => image::ImageFormat::Tga,
+        ImageFormat::Exr => image::ImageFormat::OpenExr,
+        ImageFormat::Pfm => unreachable!("pfm files are written by hand"),
+    }
+}
+
+/// Scale channels in `[0, 1]` up to `[0, max]` and round them to integers.
+/// NaN, which `clamp` would let through, becomes 0.
+fn quantize<T: num::NumCast + num::Zero>(pixels: &[f32], max: f32) -> Vec<T> {
+    pixels
+        .iter()
+        .map(|&v| {
+            let v = if v.is_nan() { 0.0 } else { v.clamp(0.0, 1.0) };
+            T::from((v * max).round()).unwrap_or_else(T::zero)
+        })
+        .collect()
+}
+
+/// Convert the color channels of `pixels` to linear light, premultiplying
+/// them by alpha if there is one.
+fn linearize(pixels: &[f32], channels: usize) -> Vec<f32> {
+    let mut linear = pixels.to_vec();
+    for pixel in linear.chunks_mut(channels) {
+        let alpha = if channels == 4 { pixel[3] } else { 1.0 };
+        for v in pixel[..3].iter_mut() {
+            *v = srgb_to_linear(*v) * alpha;
+        }
+    }
+    linear
+}
+
+/// Portable float maps are simple enough that we write them ourselves: a text
+/// header, then little-endian floats with the bottom row first.
+fn write_pfm(pixels: &[f32], bounds: (usize, usize), filename: &str) -> ImageResult<()> {
+    let mut output = BufWriter::new(File::create(filename)?);
+    write!(output, "PF\n{} {}\n-1.0\n", bounds.0, bounds.1)?;
+    for row in linearize(pixels, 3).chunks(bounds.0 * 3).rev() {
+        for &v in row {
+            output.write_all(&v.to_le_bytes())?;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+pub fn parameter_error(message: String) -> ImageError {
+    ImageError::Parameter(ParameterError::from_kind(ParameterErrorKind::Generic(message)))
+}
+
+fn encoding_error<E>(format: image::ImageFormat, error: E) -> ImageError
+    where E: Into<Box<dyn std::error::Error + Send + Sync>> {
+    ImageError::Encoding(EncodingError::new(ImageFormatHint::Exact(format), error))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_from_filename() {
+        assert_eq!(ImageFormat::from_filename("mandel.png"), Some(ImageFormat::Png));
+        assert_eq!(ImageFormat::from_filename("mandel.TIFF"), Some(ImageFormat::Tiff));
+        assert_eq!(ImageFormat::from_filename("mandel.exr"), Some(ImageFormat::Exr));
+        assert_eq!(ImageFormat::from_filename("mandel.jpg"), Some(ImageFormat::Jpeg));
+        assert_eq!(ImageFormat::from_filename("mandel.png.webp"), Some(ImageFormat::WebP));
+        assert_eq!(ImageFormat::from_filename("mandel.gif"), None);
+        assert_eq!(ImageFormat::from_filename("mandel"), None);
+    }
+
+    #[test]
+    fn test_quality_only_for_jpeg() {
+        let options = OutputOptions { quality: Some(80), ..Default::default() };
+        assert_eq!(resolve("mandel.jpg", 3, &options).unwrap(), (ImageFormat::Jpeg, Depth::Eight));
+        let error = resolve("mandel.webp", 3, &options).unwrap_err().to_string();
+        assert!(error.contains("lossless"), "{}", error);
+        assert!(resolve("mandel.png", 3, &options).is_err());
+    }
+
+    #[test]
+    fn test_png_text() {
+        let path = std::env::temp_dir().join("mandelbrot-test-text.png");
+        let filename = path.to_str().unwrap();
+        let text = vec![("Software".to_string(), "mandelbrot".to_string()), ("Scene".to_string(), "file = \"julia-\u{e9}t\u{e9}.png\"".to_string())];
+        for depth in [Depth::Eight, Depth::Sixteen] {
+            let options = OutputOptions { depth: Some(depth), text: text.clone(), ..Default::default() };
+            write_image(&[0.25; 2 * 3 * 3], (2, 3), filename, &options).unwrap();
+            assert_eq!(read_png_text(filename).unwrap(), text);
+            let image = image::open(filename).unwrap();
+            assert_eq!((image.width(), image.height()), (2, 3));
+            assert_eq!(image.to_rgb8().get_pixel(1, 2).0, [64; 3]);
+        }
+        std::fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn test_quantize() {
+        assert_eq!(quantize::<u8>(&[0.0, 0.5, 1.0, 1.5, -0.5], 255.0), vec![0, 128, 255, 255, 0]);
+        assert_eq!(quantize::<u16>(&[1.0], 65535.0), vec![65535]);
+    }
+
+    #[test]
+    fn test_quantize_non_finite() {
+        let values = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY];
+        assert_eq!(quantize::<u8>(&values, 255.0), vec![0, 255, 0]);
+        assert_eq!(quantize::<u16>(&values, 65535.0), vec![0, 65535, 0]);
+    }
+}
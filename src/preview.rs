@@ -0,0 +1,362 @@
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use color_quant::NeuQuant;
+use image::RgbaImage;
+
+use crate::error::Result;
+use crate::output::{to_image, Depth};
+use crate::request::RenderRequest;
+
+/// The width of a preview when the terminal doesn't say how wide it is.
+pub const DEFAULT_COLUMNS: usize = 80;
+
+/// How many pixels of a Sixel or Kitty preview go across one terminal
+/// column. Cells are taken to be twice as tall as they are wide.
+const CELL_WIDTH: usize = 8;
+
+/// Bytes of base64 per Kitty escape sequence, the most it accepts.
+const KITTY_CHUNK: usize = 4096;
+
+/// The NeuQuant sampling factor for Sixel palettes, as for GIF frames.
+const SIXEL_QUANTIZER_SPEED: i32 = 10;
+
+/// Characters from dark to light for ASCII previews.
+const ASCII_RAMP: &[u8] = b" .:-=+*#%@";
+
+/// How a preview is drawn in the terminal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PreviewFormat {
+    /// Half-block characters in 24-bit color, two pixels to a cell.
+    Blocks,
+    /// Sixel graphics, as xterm, foot, mlterm and others show them.
+    Sixel,
+    /// The Kitty graphics protocol, as kitty, WezTerm and Ghostty show it.
+    Kitty,
+    /// Plain characters getting denser as the image gets lighter.
+    Ascii,
+}
+
+pub const PREVIEWFORMATS: &str = "auto (default, from the terminal's environment)\nblocks\nsixel\nkitty\nascii\n";
+
+impl FromStr for PreviewFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "blocks" | "ansi" => Ok(PreviewFormat::Blocks),
+            "sixel" => Ok(PreviewFormat::Sixel),
+            "kitty" => Ok(PreviewFormat::Kitty),
+            "ascii" | "text" => Ok(PreviewFormat::Ascii),
+            _ => Err(format!("unknown preview format {}", s)),
+        }
+    }
+}
+
+impl PreviewFormat {
+    /// The best format the terminal we're running in admits to, going by
+    /// its environment variables. Terminals can't all be told apart that
+    /// way, so Sixel is only picked for those that always have it.
+    pub fn detect() -> PreviewFormat {
+        PreviewFormat::from_env(|name| std::env::var(name).ok())
+    }
+
+    fn from_env<V: Fn(&str) -> Option<String>>(var: V) -> PreviewFormat {
+        let term = var("TERM").unwrap_or_default();
+        let program = var("TERM_PROGRAM").unwrap_or_default();
+        if var("KITTY_WINDOW_ID").is_some() || term.contains("kitty") || term.contains("ghostty")
+            || program == "WezTerm" || program == "ghostty" {
+            PreviewFormat::Kitty
+        } else if term.contains("sixel") || term.starts_with("foot") || term.starts_with("mlterm") {
+            PreviewFormat::Sixel
+        } else if term.is_empty() || term == "dumb" || var("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+            PreviewFormat::Ascii
+        } else {
+            PreviewFormat::Blocks
+        }
+    }
+
+    /// How many pixels of the image one terminal cell shows, across and
+    /// down.
+    fn cell(self) -> (usize, usize) {
+        match self {
+            PreviewFormat::Blocks => (1, 2),
+            PreviewFormat::Ascii => (1, 1),
+            PreviewFormat::Sixel | PreviewFormat::Kitty => (CELL_WIDTH, 2 * CELL_WIDTH),
+        }
+    }
+}
+
+/// A small version of a render, drawn straight to the terminal.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Preview {
+    pub format: PreviewFormat,
+    /// How many terminal columns wide it is.
+    pub columns: usize,
+}
+
+impl Preview {
+    /// The request for the preview of `request`: the same region, with the
+    /// image made small enough to fit. Pixels of an ASCII preview are each a
+    /// whole cell, and so twice as tall as they are wide.
+    pub fn request(&self, request: &RenderRequest) -> RenderRequest {
+        let (across, down) = self.format.cell();
+        let (upper_left, lower_right) = (request.upper_left, request.lower_right);
+        let aspect = (upper_left.im - lower_right.im) / (lower_right.re - upper_left.re);
+        let columns = self.columns.max(1);
+        let rows = columns as f64 * aspect / 2.0;
+        let height = (rows * down as f64).round().max(1.0) as usize;
+        request.clone().size(columns * across, height)
+    }
+
+    /// Render the preview of `request` and draw it to `out`.
+    pub fn print<W: Write>(&self, out: &mut W, request: &RenderRequest) -> Result<()> {
+        let request = self.request(request);
+        let pixels = request.colorize(&request.render_escape()?);
+        let image = to_image(&pixels, request.bounds, request.coloring.channels(), Depth::Eight).into_rgba8();
+        match self.format {
+            PreviewFormat::Blocks => write_blocks(out, &image),
+            PreviewFormat::Sixel => write_sixel(out, &image),
+            PreviewFormat::Kitty => write_kitty(out, &image, self.columns),
+            PreviewFormat::Ascii => write_ascii(out, &image),
+        }?;
+        Ok(out.flush()?)
+    }
+}
+
+fn opaque(pixel: &image::Rgba<u8>) -> bool {
+    pixel.0[3] >= 128
+}
+
+/// Draw `image` with upper half blocks, the foreground color for the upper
+/// pixel of each cell and the background for the lower one. Transparent
+/// pixels are left to the terminal's own background.
+fn write_blocks<W: Write>(out: &mut W, image: &RgbaImage) -> io::Result<()> {
+    for line in block_lines(image) {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// The lines `write_blocks` draws, each ending with the colors reset.
+pub(crate) fn block_lines(image: &RgbaImage) -> Vec<String> {
+    let (width, height) = image.dimensions();
+    (0..height).step_by(2).map(|y| {
+        let mut line = String::new();
+        for x in 0..width {
+            let top = Some(image.get_pixel(x, y)).filter(|pixel| opaque(pixel));
+            let bottom = Some(y + 1).filter(|&y| y < height).map(|y| image.get_pixel(x, y)).filter(|pixel| opaque(pixel));
+            line += &match (top, bottom) {
+                (Some(top), Some(bottom)) => format!("\x1b[38;2;{};{};{}m\x1b[48;2;{};{};{}m\u{2580}",
+                                                     top[0], top[1], top[2], bottom[0], bottom[1], bottom[2]),
+                (Some(top), None) => format!("\x1b[49m\x1b[38;2;{};{};{}m\u{2580}", top[0], top[1], top[2]),
+                (None, Some(bottom)) => format!("\x1b[49m\x1b[38;2;{};{};{}m\u{2584}", bottom[0], bottom[1], bottom[2]),
+                (None, None) => "\x1b[0m ".to_string(),
+            };
+        }
+        line + "\x1b[0m"
+    }).collect()
+}
+
+/// Draw `image` one character a pixel, by how light it is.
+fn write_ascii<W: Write>(out: &mut W, image: &RgbaImage) -> io::Result<()> {
+    for row in image.rows() {
+        let line: Vec<u8> = row.map(|pixel| {
+            if !opaque(pixel) {
+                return b' '
+            }
+            let luma = (0.2126 * pixel[0] as f64 + 0.7152 * pixel[1] as f64 + 0.0722 * pixel[2] as f64) / 255.0;
+            ASCII_RAMP[((luma * ASCII_RAMP.len() as f64) as usize).min(ASCII_RAMP.len() - 1)]
+        }).collect();
+        out.write_all(&line)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Draw `image` as Sixel graphics, with a palette of up to 256 colors.
+/// Transparent pixels aren't drawn, which leaves the background.
+fn write_sixel<W: Write>(out: &mut W, image: &RgbaImage) -> io::Result<()> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let opaque_pixels: Vec<u8> = image.pixels().filter(|pixel| opaque(pixel))
+        .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+        .collect();
+    // the second parameter keeps unset pixels transparent
+    write!(out, "\x1bP0;1;0q\"1;1;{};{}", width, height)?;
+    if !opaque_pixels.is_empty() {
+        let quantizer = NeuQuant::new(SIXEL_QUANTIZER_SPEED, 256, &opaque_pixels);
+        let indices: Vec<Option<usize>> = image.pixels()
+            .map(|pixel| Some(pixel).filter(|pixel| opaque(pixel)).map(|pixel| quantizer.index_of(&pixel.0)))
+            .collect();
+        // only the colors that are used, as small images need few
+        let mut used = vec![false; 256];
+        for &index in indices.iter().flatten() {
+            used[index] = true;
+        }
+        for (index, color) in quantizer.color_map_rgb().chunks(3).enumerate().filter(|&(index, _)| used[index]) {
+            let percent = |v: u8| (v as u32 * 100 + 127) / 255;
+            write!(out, "#{};2;{};{};{}", index, percent(color[0]), percent(color[1]), percent(color[2]))?;
+        }
+
+        // six rows at a time, one pass over them for every color they use
+        for top in (0..height).step_by(6) {
+            let rows = (height - top).min(6);
+            let band = &indices[top * width..(top + rows) * width];
+            let mut colors: Vec<usize> = band.iter().flatten().copied().collect();
+            colors.sort_unstable();
+            colors.dedup();
+            for (n, &color) in colors.iter().enumerate() {
+                if n > 0 {
+                    out.write_all(b"$")?;
+                }
+                write!(out, "#{}", color)?;
+                let sixels: Vec<u8> = (0..width).map(|x| {
+                    let bits = (0..rows).filter(|&dy| band[dy * width + x] == Some(color))
+                        .fold(0, |bits, dy| bits | 1 << dy);
+                    b'?' + bits
+                }).collect();
+                write_runs(out, &sixels)?;
+            }
+            out.write_all(b"-")?;
+        }
+    }
+    out.write_all(b"\x1b\\\n")
+}
+
+/// Write Sixel characters, with runs of more than three as repeats.
+fn write_runs<W: Write>(out: &mut W, sixels: &[u8]) -> io::Result<()> {
+    let mut start = 0;
+    while start < sixels.len() {
+        let run = sixels[start..].iter().take_while(|&&c| c == sixels[start]).count();
+        if run > 3 {
+            write!(out, "!{}{}", run, sixels[start] as char)?;
+        } else {
+            out.write_all(&sixels[start..start + run])?;
+        }
+        start += run;
+    }
+    Ok(())
+}
+
+/// Draw `image` with the Kitty graphics protocol, scaled to `columns`
+/// terminal columns.
+fn write_kitty<W: Write>(out: &mut W, image: &RgbaImage, columns: usize) -> io::Result<()> {
+    let data = base64(image.as_raw());
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(KITTY_CHUNK).collect();
+    for (n, chunk) in chunks.iter().enumerate() {
+        let more = (n + 1 < chunks.len()) as u8;
+        if n == 0 {
+            // q=2 keeps the terminal from answering
+            write!(out, "\x1b_Ga=T,f=32,s={},v={},c={},q=2,m={};", image.width(), image.height(), columns, more)?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    writeln!(out)
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= group.len() {
+                text.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Rgba;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "xterm-kitty")])), PreviewFormat::Kitty);
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "xterm-256color"), ("TERM_PROGRAM", "WezTerm")])), PreviewFormat::Kitty);
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "foot")])), PreviewFormat::Sixel);
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "xterm-256color")])), PreviewFormat::Blocks);
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "xterm-256color"), ("NO_COLOR", "1")])), PreviewFormat::Ascii);
+        assert_eq!(PreviewFormat::from_env(env(&[("TERM", "dumb")])), PreviewFormat::Ascii);
+        assert_eq!(PreviewFormat::from_env(env(&[])), PreviewFormat::Ascii);
+    }
+
+    #[test]
+    fn test_preview_request() {
+        let request = RenderRequest::mandelbrot().size(4000, 3000);
+        let size = |format| Preview { format, columns: 40 }.request(&request).bounds;
+        assert_eq!(size(PreviewFormat::Blocks), (40, 30));
+        assert_eq!(size(PreviewFormat::Ascii), (40, 15));
+        assert_eq!(size(PreviewFormat::Kitty), (320, 240));
+    }
+
+    #[test]
+    fn test_blocks() {
+        let mut image = RgbaImage::from_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let mut out = Vec::new();
+        write_blocks(&mut out, &image).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m\u{2580}\x1b[49m\x1b[38;2;255;0;0m\u{2584}\x1b[0m");
+        assert_eq!(lines[1], "\x1b[49m\x1b[38;2;255;0;0m\u{2580}\x1b[49m\x1b[38;2;255;0;0m\u{2580}\x1b[0m");
+    }
+
+    #[test]
+    fn test_ascii() {
+        let mut image = RgbaImage::from_pixel(3, 1, Rgba([255, 255, 255, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 255, 255, 0]));
+        let mut out = Vec::new();
+        write_ascii(&mut out, &image).unwrap();
+        assert_eq!(out, b"  @\n");
+    }
+
+    #[test]
+    fn test_sixel() {
+        let mut image = RgbaImage::from_pixel(5, 7, Rgba([0, 0, 255, 255]));
+        image.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let mut out = Vec::new();
+        write_sixel(&mut out, &image).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\x1bP0;1;0q\"1;1;5;7#"));
+        assert_eq!(text.matches(";2;").count(), 1);
+        assert!(text.ends_with("\x1b\\\n"));
+        // two bands in the one color, the first missing its top left pixel
+        // and the second one row of five
+        let bands: Vec<&str> = text.split('-').collect();
+        assert_eq!(bands.len(), 3);
+        let color = &bands[1][..bands[1].find('!').unwrap()];
+        assert!(bands[0].ends_with(&format!("{}}}!4~", color)), "{:?}", bands[0]);
+        assert_eq!(bands[1], format!("{}!5@", color));
+    }
+
+    #[test]
+    fn test_kitty() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+
+        let image = RgbaImage::from_pixel(40, 40, Rgba([1, 2, 3, 255]));
+        let mut out = Vec::new();
+        write_kitty(&mut out, &image, 5).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with("\x1b_Ga=T,f=32,s=40,v=40,c=5,q=2,m=1;AQID/wECA/8"));
+        // 6400 bytes are 8536 of base64, which takes three chunks
+        assert_eq!(text.matches("\x1b_G").count(), 3);
+        assert!(text.contains("\x1b_Gm=0;"));
+    }
+}
@@ -0,0 +1,205 @@
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::escape::EscapeSample;
+
+/// How progress is reported on stderr.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProgressStyle {
+    /// A bar redrawn in place, with the ETA and throughput.
+    Bar,
+    /// One JSON object per line, for other programs to read.
+    Json,
+    /// Nothing at all.
+    Quiet,
+}
+
+pub const PROGRESSSTYLES: &str = "bar (default on a terminal)\njson\nnone (default otherwise)\n";
+
+impl FromStr for ProgressStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "bar" => Ok(ProgressStyle::Bar),
+            "json" => Ok(ProgressStyle::Json),
+            "none" | "quiet" => Ok(ProgressStyle::Quiet),
+            _ => Err(format!("unknown progress style {}", s)),
+        }
+    }
+}
+
+impl Default for ProgressStyle {
+    fn default() -> Self {
+        if io::stderr().is_terminal() { ProgressStyle::Bar } else { ProgressStyle::Quiet }
+    }
+}
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Stop rendering at the first Ctrl-C, so that whatever is finished can be
+/// saved, and give up at once on the second.
+pub fn catch_interrupt() -> io::Result<()> {
+    let result = ctrlc::set_handler(|| {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            std::process::exit(130);
+        }
+    });
+    result.map_err(|e| io::Error::other(format!("can't catch Ctrl-C: {}", e)))
+}
+
+/// Whether rendering has been asked to stop. Renderers check this before
+/// starting each piece of work, so anything they have started is finished.
+pub fn cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+/// The iterations that went into `samples`, counting interior points as the
+/// whole `limit` whether or not they were actually iterated.
+pub fn iterations(samples: &[EscapeSample], limit: usize) -> u64 {
+    samples.iter().map(|s| if s.interior { limit as u64 } else { s.count as u64 }).sum()
+}
+
+/// How often the bar is redrawn.
+const BAR_INTERVAL: Duration = Duration::from_millis(100);
+/// How often a JSON progress line is written.
+const JSON_INTERVAL: Duration = Duration::from_millis(500);
+const BAR_WIDTH: usize = 30;
+
+/// Counts finished pieces of a render, which may be tiles, strips or
+/// anything else named by `unit`, and reports on them. It can be shared
+/// between threads.
+pub struct Progress {
+    style: ProgressStyle,
+    unit: &'static str,
+    limit: usize,
+    total: u64,
+    total_pixels: u64,
+    done: AtomicU64,
+    pixels: AtomicU64,
+    iterations: AtomicU64,
+    start: Instant,
+    last_report: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    /// Progress on `total` pieces covering `total_pixels`, iterated up to
+    /// `limit`.
+    pub fn new(style: ProgressStyle, unit: &'static str, total: usize, total_pixels: usize, limit: usize) -> Progress {
+        Progress {
+            style,
+            unit,
+            limit,
+            total: total as u64,
+            total_pixels: total_pixels as u64,
+            done: AtomicU64::new(0),
+            pixels: AtomicU64::new(0),
+            iterations: AtomicU64::new(0),
+            start: Instant::now(),
+            last_report: Mutex::new(None),
+        }
+    }
+
+    /// Count one finished piece holding `samples`.
+    pub fn record(&self, samples: &[EscapeSample]) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.pixels.fetch_add(samples.len() as u64, Ordering::Relaxed);
+        self.iterations.fetch_add(iterations(samples, self.limit), Ordering::Relaxed);
+
+        let interval = match self.style {
+            ProgressStyle::Bar => BAR_INTERVAL,
+            ProgressStyle::Json => JSON_INTERVAL,
+            ProgressStyle::Quiet => return,
+        };
+        let mut last = self.last_report.lock().unwrap();
+        if last.is_none_or(|last| last.elapsed() >= interval) {
+            *last = Some(Instant::now());
+            self.report("progress");
+        }
+    }
+
+    /// Report the final numbers, noting whether the render was cut short.
+    pub fn finish(&self) {
+        self.report(if cancelled() { "cancelled" } else { "done" });
+        if self.style == ProgressStyle::Bar {
+            eprintln!();
+        }
+    }
+
+    fn report(&self, event: &str) {
+        let done = self.done.load(Ordering::Relaxed);
+        let pixels = self.pixels.load(Ordering::Relaxed);
+        let iterations = self.iterations.load(Ordering::Relaxed);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let fraction = if self.total_pixels == 0 { 1.0 } else { pixels as f64 / self.total_pixels as f64 };
+        let eta = if pixels == 0 { None } else { Some(elapsed * (1.0 - fraction) / fraction) };
+        let (mpixels, giters) = if elapsed > 0.0 {
+            (pixels as f64 / elapsed / 1e6, iterations as f64 / elapsed / 1e9)
+        } else {
+            (0.0, 0.0)
+        };
+
+        let mut stderr = io::stderr().lock();
+        // progress is best effort, so a closed stderr is not worth stopping for
+        let _ = match self.style {
+            ProgressStyle::Bar => {
+                let filled = ((fraction * BAR_WIDTH as f64) as usize).min(BAR_WIDTH);
+                // the trailing spaces clear anything left from a longer line
+                write!(stderr, "\r[{}{}] {:5.1}%  {}/{} {}  {:.1} Mpixel/s  {:.2} Giter/s  {}   ",
+                       "#".repeat(filled), "-".repeat(BAR_WIDTH - filled), fraction * 100.0,
+                       done, self.total, self.unit, mpixels, giters,
+                       match (event, eta) {
+                           ("progress", Some(eta)) => format!("ETA {}", format_duration(eta)),
+                           ("progress", None) => "ETA ?".to_string(),
+                           _ => format!("{} in {}", event, format_duration(elapsed)),
+                       })
+            }
+            ProgressStyle::Json => writeln!(stderr,
+                "{{\"event\":\"{}\",\"unit\":\"{}\",\"done\":{},\"total\":{},\"pixels\":{},\"total_pixels\":{},\
+                 \"iterations\":{},\"elapsed\":{:.3},\"eta\":{},\"mpixels_per_sec\":{:.3},\"giters_per_sec\":{:.4}}}",
+                event, self.unit, done, self.total, pixels, self.total_pixels, iterations, elapsed,
+                eta.map_or("null".to_string(), |eta| format!("{:.3}", eta)), mpixels, giters),
+            ProgressStyle::Quiet => Ok(()),
+        };
+        let _ = stderr.flush();
+    }
+}
+
+/// Seconds as `1h02m03s`, `2m03s` or `3s`.
+fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h{:02}m{:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m{:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fractal::LIMIT;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(3.4), "3s");
+        assert_eq!(format_duration(123.0), "2m03s");
+        assert_eq!(format_duration(3723.0), "1h02m03s");
+    }
+
+    #[test]
+    fn test_record_counts() {
+        let progress = Progress::new(ProgressStyle::Quiet, "tiles", 2, 4, LIMIT);
+        let escaped = EscapeSample { count: 5, interior: false, ..EscapeSample::INTERIOR };
+        progress.record(&[escaped, EscapeSample::INTERIOR]);
+        assert_eq!(progress.done.load(Ordering::Relaxed), 1);
+        assert_eq!(progress.pixels.load(Ordering::Relaxed), 2);
+        assert_eq!(progress.iterations.load(Ordering::Relaxed), 5 + LIMIT as u64);
+    }
+}
@@ -0,0 +1,333 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use image::ImageResult;
+use num::Complex;
+use rayon::prelude::*;
+
+use crate::color::{colorize, AlphaMode, Coloring};
+use crate::error::Error;
+use crate::escape::{EscapeField, EscapeSample};
+use crate::fractal::{subpixel_to_point, Fractal, Orbit};
+use crate::output::{parameter_error, write_image, ImageFormat, OutputOptions};
+use crate::progress::{self, Progress, ProgressStyle};
+use crate::scheduler::{Scheduler, Tile};
+
+/// How the tiles of a pyramid are laid out on disk.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Layout {
+    /// `z/x/y.png`, as used by slippy maps. Zoom 0 is a single tile holding
+    /// the whole region, and every zoom level doubles the tiles across.
+    Xyz,
+    /// Deep Zoom: a `.dzi` description next to a `_files` directory holding
+    /// `level/column_row.png`, from a single pixel up to the full size.
+    DeepZoom,
+}
+
+pub const LAYOUTS: &str = "xyz (default)\ndzi\n";
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "xyz" => Ok(Layout::Xyz),
+            "dzi" | "deepzoom" => Ok(Layout::DeepZoom),
+            _ => Err(format!("unknown tile layout {}", s)),
+        }
+    }
+}
+
+pub const DEFAULT_TILE_SIZE: usize = 256;
+pub const DEFAULT_MAX_ZOOM: usize = 5;
+
+/// One resolution of a pyramid: the region as a `bounds` image between
+/// `upper_left` and `lower_right`, cut into square tiles.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Level {
+    pub zoom: usize,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+}
+
+/// A multi-resolution set of tiles covering a region of the plane, each
+/// rendered at its own resolution rather than shrunk from a bigger one.
+pub struct Pyramid<'a> {
+    pub fractal: Fractal,
+    pub orbit: Orbit,
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// How far the region is turned, in degrees. Tiles are laid out along
+    /// the plane's axes, so `check` only lets this be 0.
+    pub rotation: f64,
+    pub layout: Layout,
+    /// The width and height of a tile, in pixels.
+    pub tile_size: usize,
+    /// At this zoom, the longer side of the region spans `2^max_zoom` tiles.
+    pub max_zoom: usize,
+    /// How tiles are rendered: its acceleration and iteration limit, but not
+    /// its tile size or order, as the pyramid cuts its own tiles.
+    pub scheduler: Scheduler,
+    pub coloring: &'a Coloring,
+}
+
+/// What `Pyramid::export` did.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ExportSummary {
+    pub written: usize,
+    pub skipped: usize,
+}
+
+impl<'a> Pyramid<'a> {
+    /// Make sure the region isn't turned, and the deepest level isn't too
+    /// big for its pixels to be counted, which with 256 pixel tiles on a
+    /// 64-bit machine is past zoom 23.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.rotation != 0.0 {
+            return Err(Error::BadOption {
+                option: "rotation",
+                value: self.rotation.to_string(),
+                reason: "tiles follow the axes of the plane, so the region can't be turned".to_string(),
+            });
+        }
+        match self.span() {
+            Some(_) => Ok(()),
+            None => Err(Error::BadOption {
+                option: "--max-zoom",
+                value: self.max_zoom.to_string(),
+                reason: format!("tiles {} pixels across can't be counted that far down", self.tile_size),
+            }),
+        }
+    }
+
+    /// The pixels across the longer side of the deepest level, if that many
+    /// squared, and then the levels above it too, can be counted.
+    fn span(&self) -> Option<usize> {
+        let span = u32::try_from(self.max_zoom).ok()
+            .and_then(|zoom| 1usize.checked_shl(zoom))
+            .and_then(|tiles| tiles.checked_mul(self.tile_size))?;
+        // the levels above the deepest add a third as many pixels again
+        span.checked_mul(span)?.checked_mul(2)?;
+        Some(span)
+    }
+
+    /// The levels from zoom 0 down, which are only worth making once
+    /// `check` has passed.
+    pub fn levels(&self) -> Vec<Level> {
+        let (width, height) = (self.lower_right.re - self.upper_left.re, self.upper_left.im - self.lower_right.im);
+        let extent = width.max(height);
+        let size = self.tile_size;
+        match self.layout {
+            Layout::Xyz => (0..=self.max_zoom).map(|zoom| {
+                // whole tiles only, so the level may reach past the region
+                // on its shorter side
+                let tile_extent = extent / (zoom as f64).exp2();
+                let across = ((width / tile_extent - 1e-9).ceil() as usize).max(1);
+                let down = ((height / tile_extent - 1e-9).ceil() as usize).max(1);
+                Level {
+                    zoom,
+                    bounds: (across * size, down * size),
+                    upper_left: self.upper_left,
+                    lower_right: Complex {
+                        re: self.upper_left.re + across as f64 * tile_extent,
+                        im: self.upper_left.im - down as f64 * tile_extent,
+                    },
+                }
+            }).collect(),
+            Layout::DeepZoom => {
+                let scale = size as f64 * (self.max_zoom as f64).exp2() / extent;
+                let full = (((width * scale).round() as usize).max(1), ((height * scale).round() as usize).max(1));
+                let top = full.0.max(full.1).next_power_of_two().trailing_zeros() as usize;
+                (0..=top).map(|zoom| {
+                    let shrink = 1 << (top - zoom);
+                    Level {
+                        zoom,
+                        bounds: (full.0.div_ceil(shrink), full.1.div_ceil(shrink)),
+                        upper_left: self.upper_left,
+                        lower_right: self.lower_right,
+                    }
+                }).collect()
+            }
+        }
+    }
+
+    /// Where the tile in column `x` and row `y` of `level` is written, under
+    /// `output`.
+    fn tile_path(&self, output: &Path, level: &Level, x: usize, y: usize) -> PathBuf {
+        match self.layout {
+            Layout::Xyz => output.join(level.zoom.to_string()).join(x.to_string()).join(format!("{}.png", y)),
+            Layout::DeepZoom => deep_zoom_files(output).join(level.zoom.to_string()).join(format!("{}_{}.png", x, y)),
+        }
+    }
+
+    /// Render every tile of the pyramid into `output`, a directory for
+    /// `Layout::Xyz` or the `.dzi` file for `Layout::DeepZoom`, reporting
+    /// progress in `style`. Tiles that are already there are left alone, so
+    /// an interrupted export can be picked up again by running it once more.
+    pub fn export(&self, output: &Path, style: ProgressStyle) -> ImageResult<ExportSummary> {
+        if self.coloring.alpha == Some(AlphaMode::Escape) {
+            return Err(parameter_error("escape alpha is scaled to each image, so tiles wouldn't match".to_string()));
+        }
+        if self.tile_size == 0 {
+            return Err(parameter_error("tiles must be at least one pixel across".to_string()));
+        }
+        self.check().map_err(|e| parameter_error(e.to_string()))?;
+        let levels = self.levels();
+        if self.layout == Layout::DeepZoom {
+            let full = levels.last().unwrap().bounds;
+            if let Some(dir) = output.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::write(output, format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"png\" Overlap=\"0\" TileSize=\"{}\">\n  \
+                 <Size Width=\"{}\" Height=\"{}\"/>\n\
+                 </Image>\n", self.tile_size, full.0, full.1))?;
+        }
+
+        // the deepest levels hold far too many tiles to list, so they are
+        // gone through once to count the missing ones and again to render
+        let missing = |&(level, x, y): &(&Level, usize, usize)| !self.tile_path(output, level, x, y).exists();
+        let (mut tiles, mut count, mut pixels) = (0, 0, 0);
+        for (level, x, y) in self.tiles(&levels) {
+            tiles += 1;
+            if missing(&(level, x, y)) {
+                count += 1;
+                pixels += self.tile(level, x, y).len();
+            }
+        }
+        let progress = Progress::new(style, "tiles", count, pixels, self.scheduler.limit);
+        let written = self.tiles(&levels).filter(missing).par_bridge().map(|(level, x, y)| -> ImageResult<usize> {
+            if progress::cancelled() {
+                return Ok(0)
+            }
+            self.write_tile(level, x, y, &self.tile_path(output, level, x, y), &progress)?;
+            Ok(1)
+        }).try_reduce(|| 0, |a, b| Ok(a + b))?;
+        progress.finish();
+        Ok(ExportSummary { written, skipped: tiles - count })
+    }
+
+    /// Every tile of `levels` as its level, column and row, a level at a
+    /// time.
+    fn tiles<'l>(&self, levels: &'l [Level]) -> impl Iterator<Item = (&'l Level, usize, usize)> + 'l {
+        let size = self.tile_size;
+        levels.iter().flat_map(move |level| (0..level.bounds.0.div_ceil(size))
+            .flat_map(move |x| (0..level.bounds.1.div_ceil(size)).map(move |y| (level, x, y))))
+    }
+
+    /// The pixels of `level` covered by the tile in column `x` and row `y`.
+    fn tile(&self, level: &Level, x: usize, y: usize) -> Tile {
+        let size = self.tile_size;
+        Tile {
+            x: x * size,
+            y: y * size,
+            width: size.min(level.bounds.0 - x * size),
+            height: size.min(level.bounds.1 - y * size),
+        }
+    }
+
+    fn write_tile(&self, level: &Level, x: usize, y: usize, path: &Path, progress: &Progress) -> ImageResult<()> {
+        let tile = self.tile(level, x, y);
+        let corner = |col: usize, row: usize| subpixel_to_point(level.bounds, (col as f64, row as f64), level.upper_left, level.lower_right);
+        let mut field = EscapeField::new((tile.width, tile.height),
+                                         corner(tile.x, tile.y),
+                                         corner(tile.x + tile.width, tile.y + tile.height));
+        field.samples = vec![EscapeSample::INTERIOR; tile.len()];
+        self.scheduler.render_tile(self.fractal, self.orbit, self.rotation, &mut field.samples, &tile, level.bounds, level.upper_left, level.lower_right);
+        progress.record(&field.samples);
+
+        // written under another name first, so a run that is stopped partway
+        // never leaves a broken tile that would be skipped next time
+        fs::create_dir_all(path.parent().unwrap())?;
+        let partial = path.with_extension("png.part");
+        let options = OutputOptions { format: Some(ImageFormat::Png), ..Default::default() };
+        write_image(&colorize(&field, self.coloring), field.bounds, partial.to_str().unwrap(), &options)?;
+        fs::rename(partial, path)?;
+        Ok(())
+    }
+}
+
+/// The directory Deep Zoom keeps the tiles of `mandel.dzi` in,
+/// `mandel_files`.
+fn deep_zoom_files(dzi: &Path) -> PathBuf {
+    let stem = dzi.file_stem().and_then(|s| s.to_str()).unwrap_or("pyramid");
+    dzi.with_file_name(format!("{}_files", stem))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color;
+
+    fn pyramid(layout: Layout, coloring: &Coloring) -> Pyramid<'_> {
+        Pyramid {
+            fractal: Fractal::Mandelbrot,
+            orbit: Orbit::default(),
+            upper_left: Complex { re: -2.0, im: 1.0 },
+            lower_right: Complex { re: 1.0, im: -1.0 },
+            rotation: 0.0,
+            layout,
+            tile_size: 16,
+            max_zoom: 2,
+            scheduler: Scheduler::default(),
+            coloring,
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let coloring = Coloring { palette: color::colors("wikipedia").unwrap(), mode: color::ColorMode::Count, alpha: None, offset: 0.0 };
+        let xyz = pyramid(Layout::Xyz, &coloring).levels();
+        assert_eq!(xyz.iter().map(|l| l.bounds).collect::<Vec<_>>(), vec![(16, 16), (32, 32), (64, 48)]);
+        assert_eq!(xyz[2].lower_right, Complex { re: 1.0, im: -1.25 });
+
+        let dzi = pyramid(Layout::DeepZoom, &coloring).levels();
+        assert_eq!(dzi.len(), 7);
+        assert_eq!(dzi[0].bounds, (1, 1));
+        assert_eq!(dzi[6].bounds, (64, 43));
+        assert_eq!(dzi[5].bounds, (32, 22));
+    }
+
+    #[test]
+    fn test_export_skips_existing_tiles() {
+        let coloring = Coloring { palette: color::colors("wikipedia").unwrap(), mode: color::ColorMode::Count, alpha: None, offset: 0.0 };
+        let dir = std::env::temp_dir().join("mandelbrot-test-pyramid");
+        let _ = fs::remove_dir_all(&dir);
+        let pyramid = pyramid(Layout::Xyz, &coloring);
+        assert_eq!(pyramid.export(&dir, ProgressStyle::Quiet).unwrap(), ExportSummary { written: 1 + 4 + 12, skipped: 0 });
+        assert!(dir.join("2").join("3").join("2.png").exists());
+
+        fs::remove_file(dir.join("1").join("0").join("1.png")).unwrap();
+        assert_eq!(pyramid.export(&dir, ProgressStyle::Quiet).unwrap(), ExportSummary { written: 1, skipped: 16 });
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_max_zoom_too_deep() {
+        let coloring = Coloring { palette: color::colors("wikipedia").unwrap(), mode: color::ColorMode::Count, alpha: None, offset: 0.0 };
+        for layout in [Layout::Xyz, Layout::DeepZoom] {
+            for max_zoom in [24, 64, 70, usize::MAX] {
+                let pyramid = Pyramid { max_zoom, tile_size: 256, ..pyramid(layout, &coloring) };
+                assert!(matches!(pyramid.check(), Err(Error::BadOption { option: "--max-zoom", .. })));
+                let dir = std::env::temp_dir().join("mandelbrot-test-pyramid-deep");
+                assert!(pyramid.export(&dir, ProgressStyle::Quiet).is_err());
+                assert!(!dir.exists());
+            }
+        }
+        let pyramid = Pyramid { max_zoom: 23, tile_size: 256, ..pyramid(Layout::Xyz, &coloring) };
+        assert!(pyramid.check().is_ok());
+    }
+
+    #[test]
+    fn test_turned_region() {
+        let coloring = Coloring { palette: color::colors("wikipedia").unwrap(), mode: color::ColorMode::Count, alpha: None, offset: 0.0 };
+        let pyramid = Pyramid { rotation: 30.0, ..pyramid(Layout::Xyz, &coloring) };
+        assert!(matches!(pyramid.check(), Err(Error::BadOption { option: "rotation", .. })));
+        let dir = std::env::temp_dir().join("mandelbrot-test-pyramid-turned");
+        assert!(pyramid.export(&dir, ProgressStyle::Quiet).is_err());
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,287 @@
+use image::{RgbImage, RgbaImage};
+use num::Complex;
+
+use crate::accel::Accel;
+use crate::antialias::{antialias, Antialias};
+use crate::color::{self, colorize, AlphaMode, ColorMode, Coloring, Palette};
+use crate::error::{Error, Result};
+use crate::escape::{EscapeField, EscapeSample};
+use crate::fractal::{check_viewport, Fractal, Orbit};
+use crate::output::{to_image, Depth};
+use crate::scheduler::{Scheduler, Tile};
+
+/// Everything needed to render one image, built up a setting at a time:
+///
+/// ```
+/// use mandelbrot::{color, RenderRequest};
+///
+/// let image = RenderRequest::mandelbrot()
+///     .size(320, 240)
+///     .center(num::Complex { re: -0.75, im: 0.1 }, 0.5)
+///     .palette(color::colors("magma")?)
+///     .iterations(1000)
+///     .render_rgb()?;
+/// assert_eq!(image.dimensions(), (320, 240));
+/// # Ok::<(), mandelbrot::Error>(())
+/// ```
+///
+/// Anything not set keeps the same default as the command line.
+#[derive(Clone, Debug)]
+pub struct RenderRequest {
+    pub fractal: Fractal,
+    /// How every point's orbit is followed.
+    pub orbit: Orbit,
+    /// The width and height of the image, in pixels.
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// How far the image is turned counterclockwise about the center of its
+    /// region, in degrees.
+    pub rotation: f64,
+    pub coloring: Coloring,
+    pub scheduler: Scheduler,
+    /// Supersampling along edges, if any.
+    pub antialias: Option<Antialias>,
+}
+
+impl RenderRequest {
+    /// An 800x600 image of `fractal`, showing all of it.
+    pub fn new(fractal: Fractal) -> RenderRequest {
+        let (upper_left, lower_right) = match fractal {
+            Fractal::Julia(_) => (Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 }),
+            Fractal::Mandelbrot | Fractal::BurningShip => (Complex { re: -2.5, im: 1.5 }, Complex { re: 1.5, im: -1.5 }),
+        };
+        RenderRequest {
+            fractal,
+            orbit: Orbit::default(),
+            bounds: (800, 600),
+            upper_left,
+            lower_right,
+            rotation: 0.0,
+            coloring: Coloring {
+                palette: color::colors("wikipedia").unwrap().with_cache(color::DEFAULT_RESOLUTION),
+                mode: ColorMode::Count,
+                alpha: None,
+                offset: 0.0,
+            },
+            scheduler: Scheduler::default(),
+            antialias: None,
+        }
+    }
+
+    pub fn mandelbrot() -> RenderRequest {
+        RenderRequest::new(Fractal::Mandelbrot)
+    }
+
+    pub fn julia(seed: Complex<f64>) -> RenderRequest {
+        RenderRequest::new(Fractal::Julia(seed))
+    }
+
+    pub fn burning_ship() -> RenderRequest {
+        RenderRequest::new(Fractal::BurningShip)
+    }
+
+    pub fn size(mut self, width: usize, height: usize) -> RenderRequest {
+        self.bounds = (width, height);
+        self
+    }
+
+    /// Show the region between `upper_left` and `lower_right`, as they are.
+    pub fn viewport(mut self, upper_left: Complex<f64>, lower_right: Complex<f64>) -> RenderRequest {
+        self.upper_left = upper_left;
+        self.lower_right = lower_right;
+        self
+    }
+
+    /// Show a region `width` across centered on `center`, as tall as the
+    /// image's shape calls for. Set the size first.
+    pub fn center(self, center: Complex<f64>, width: f64) -> RenderRequest {
+        let height = width * self.bounds.1 as f64 / self.bounds.0 as f64;
+        let half = Complex { re: width / 2.0, im: -height / 2.0 };
+        self.viewport(center - half, center + half)
+    }
+
+    /// Color with `palette`, which is used as it is, so give it a cache with
+    /// `Palette::with_cache` if it should have one.
+    pub fn palette(mut self, palette: Palette) -> RenderRequest {
+        self.coloring.palette = palette;
+        self
+    }
+
+    pub fn mode(mut self, mode: ColorMode) -> RenderRequest {
+        self.coloring.mode = mode;
+        self
+    }
+
+    /// Add an alpha channel, made by `alpha`.
+    pub fn alpha(mut self, alpha: AlphaMode) -> RenderRequest {
+        self.coloring.alpha = Some(alpha);
+        self
+    }
+
+    /// Move every color `offset` trips around the palette.
+    pub fn palette_offset(mut self, offset: f64) -> RenderRequest {
+        self.coloring.offset = offset;
+        self
+    }
+
+    /// Set how many iterations a point gets before it is taken to be inside.
+    pub fn iterations(mut self, limit: usize) -> RenderRequest {
+        self.scheduler.limit = limit;
+        self
+    }
+
+    /// Raise the orbit to `power` at every step rather than squaring it.
+    /// Powers that aren't whole numbers take the principal branch.
+    pub fn power(mut self, power: f64) -> RenderRequest {
+        self.orbit.power = power;
+        self
+    }
+
+    /// Set how far from the origin an orbit has to get to escape.
+    pub fn bailout(mut self, bailout: f64) -> RenderRequest {
+        self.orbit.bailout = bailout;
+        self
+    }
+
+    /// Turn the view `degrees` counterclockwise about the center of its
+    /// region.
+    pub fn rotation(mut self, degrees: f64) -> RenderRequest {
+        self.rotation = degrees;
+        self
+    }
+
+    pub fn accel(mut self, accel: Accel) -> RenderRequest {
+        self.scheduler.accel = accel;
+        self
+    }
+
+    pub fn tile_size(mut self, tile_size: usize) -> RenderRequest {
+        self.scheduler.tile_size = tile_size;
+        self
+    }
+
+    pub fn antialias(mut self, settings: Antialias) -> RenderRequest {
+        self.antialias = Some(settings);
+        self
+    }
+
+    /// Check that the image has some pixels and the region some area, that
+    /// the power and bailout make sense, and that the rotation is a number.
+    pub fn validate(&self) -> Result<()> {
+        if self.bounds.0 == 0 || self.bounds.1 == 0 {
+            return Err(Error::BadDimensions(format!("{}x{}", self.bounds.0, self.bounds.1)));
+        }
+        check_viewport(self.upper_left, self.lower_right)?;
+        if let Err(reason) = self.orbit.check() {
+            let value = format!("power {}, bailout {}", self.orbit.power, self.orbit.bailout);
+            return Err(Error::BadOption { option: "orbit", value, reason });
+        }
+        if !self.rotation.is_finite() {
+            return Err(Error::BadOption { option: "rotation", value: self.rotation.to_string(),
+                                          reason: "it has to be a number of degrees".to_string() });
+        }
+        Ok(())
+    }
+
+    /// A field of the right size and region for the image, not yet rendered.
+    pub fn field(&self) -> EscapeField {
+        EscapeField::new(self.bounds, self.upper_left, self.lower_right)
+    }
+
+    /// Render the escape samples of every pixel.
+    pub fn render_escape(&self) -> Result<EscapeField> {
+        self.render_escape_with(|_, _| ())
+    }
+
+    /// Like `render_escape`, calling `on_tile` with each tile and its samples
+    /// as it finishes, as `Scheduler::render` does.
+    pub fn render_escape_with<F>(&self, on_tile: F) -> Result<EscapeField>
+        where F: FnMut(&Tile, &[EscapeSample]) {
+        self.validate()?;
+        let mut field = self.field();
+        self.scheduler.render(&mut field, self.fractal, self.orbit, self.rotation, on_tile);
+        Ok(field)
+    }
+
+    /// Color `field`, rendered for this request, and supersample its edges,
+    /// giving `coloring.channels()` values per pixel.
+    pub fn colorize(&self, field: &EscapeField) -> Vec<f32> {
+        let mut pixels = colorize(field, &self.coloring);
+        if let Some(settings) = self.antialias {
+            antialias(&mut pixels, field, &self.coloring, self.fractal, &self.scheduler, self.orbit, self.rotation, &settings);
+        }
+        pixels
+    }
+
+    /// Render the image with 8 bits per channel, dropping any alpha channel.
+    pub fn render_rgb(&self) -> Result<RgbImage> {
+        let pixels = self.colorize(&self.render_escape()?);
+        Ok(to_image(&pixels, self.bounds, self.coloring.channels(), Depth::Eight).into_rgb8())
+    }
+
+    /// Render the image with 8 bits per channel and an alpha channel, which
+    /// is opaque unless one was asked for.
+    pub fn render_rgba(&self) -> Result<RgbaImage> {
+        let pixels = self.colorize(&self.render_escape()?);
+        Ok(to_image(&pixels, self.bounds, self.coloring.channels(), Depth::Eight).into_rgba8())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_center() {
+        let request = RenderRequest::mandelbrot().size(200, 100).center(Complex { re: -1.0, im: 0.5 }, 2.0);
+        assert_eq!(request.upper_left, Complex { re: -2.0, im: 1.0 });
+        assert_eq!(request.lower_right, Complex { re: 0.0, im: 0.0 });
+    }
+
+    #[test]
+    fn test_render_rgb_matches_field() {
+        let request = RenderRequest::julia(Complex { re: -0.8, im: 0.156 })
+            .size(40, 30)
+            .iterations(100)
+            .mode(ColorMode::Smooth)
+            .alpha(AlphaMode::Interior);
+        let field = request.render_escape().unwrap();
+        assert_eq!(field.bounds, (40, 30));
+        assert!(field.samples.iter().all(|s| s.interior || s.count < 100));
+
+        let rgba = request.render_rgba().unwrap();
+        let pixels = colorize(&field, &request.coloring);
+        assert_eq!(rgba.as_raw(), to_image(&pixels, field.bounds, 4, Depth::Eight).as_bytes());
+        let rgb = request.render_rgb().unwrap();
+        assert_eq!(rgb.dimensions(), (40, 30));
+        assert_eq!(rgb.get_pixel(7, 3).0, rgba.get_pixel(7, 3).0[..3]);
+    }
+
+    #[test]
+    fn test_rotation() {
+        // a quarter turn of a square view moves pixel corner (x, y) to where
+        // (y, n - x) was
+        let n = 64;
+        let request = RenderRequest::julia(Complex { re: -0.8, im: 0.156 }).size(n, n).center(Complex { re: 0.1, im: -0.2 }, 3.0);
+        let still = request.render_escape().unwrap();
+        let turned = request.rotation(90.0).render_escape().unwrap();
+        let same = (0..n).flat_map(|y| (1..n).map(move |x| (x, y)))
+            .filter(|&(x, y)| turned.samples[y * n + x].count == still.samples[(n - x) * n + y].count)
+            .count();
+        assert!(same as f64 >= 0.99 * (n * (n - 1)) as f64, "{} of {}", same, n * (n - 1));
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        assert!(matches!(RenderRequest::mandelbrot().size(0, 10).render_escape(), Err(Error::BadDimensions(_))));
+        let flat = RenderRequest::mandelbrot().viewport(Complex { re: -1.0, im: 0.5 }, Complex { re: 1.0, im: 0.5 });
+        assert!(matches!(flat.render_rgb(), Err(Error::EmptyViewport(..))));
+        let small = RenderRequest::mandelbrot().size(10, 10).power(1.0);
+        assert!(matches!(small.render_escape(), Err(Error::BadOption { option: "orbit", .. })));
+        let inside = RenderRequest::mandelbrot().size(10, 10).bailout(0.5);
+        assert!(matches!(inside.render_escape(), Err(Error::BadOption { option: "orbit", .. })));
+        let spun = RenderRequest::mandelbrot().size(10, 10).rotation(f64::INFINITY);
+        assert!(matches!(spun.render_escape(), Err(Error::BadOption { option: "rotation", .. })));
+    }
+}
@@ -0,0 +1,400 @@
+use std::fs;
+use std::path::Path;
+
+use num::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::antialias::Antialias;
+use crate::color::{self, AlphaMode, ColorMode, Coloring};
+use crate::error::{Error, Result};
+use crate::fractal::{Fractal, Orbit, BAILOUT, DEFAULT_SEED, POWER};
+use crate::output::{read_png_text, Depth, ImageFormat, OutputOptions};
+use crate::request::RenderRequest;
+use crate::scheduler::Scheduler;
+
+/// The keyword of the PNG text chunk that holds the scene of an image.
+pub const SCENE_KEYWORD: &str = "Scene";
+
+/// Every setting of a render, as it is saved to a scene file, so the same
+/// image can be made again from the file alone:
+///
+/// ```toml
+/// [fractal]
+/// type = "julia"
+/// seed = [-0.4, 0.6]
+///
+/// [view]
+/// size = [1000, 1000]
+/// upper_left = [-2.0, 2.0]
+/// lower_right = [2.0, -2.0]
+///
+/// [render]
+/// iterations = 1000
+///
+/// [color]
+/// palette = "vaportest"
+/// mode = "smooth"
+///
+/// [output]
+/// file = "julia.png"
+/// ```
+///
+/// Only `fractal`, `view` and `output` have to be there; anything else left
+/// out has the same default as on the command line. Options take the same
+/// values as their command line counterparts, except that `alpha =
+/// "distance=2"` is written `alpha = { distance = 2.0 }`. A few settings are
+/// only found here: the view's `rotation` in degrees, and the `power` each
+/// step raises the orbit to and the `bailout` radius it has to leave, both 2
+/// unless `fractal` sets them.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    pub fractal: FractalSpec,
+    pub view: View,
+    #[serde(default)]
+    pub render: Scheduler,
+    #[serde(default)]
+    pub color: ColorSpec,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub antialias: Option<Antialias>,
+    pub output: OutputSpec,
+}
+
+/// Whether a scene is a Mandelbrot set or a Julia set.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FractalType {
+    Mandelbrot,
+    Julia,
+}
+
+/// The function iterated.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Formula {
+    /// `z^2 + c`, or `z` to the fractal's power
+    #[default]
+    Quadratic,
+    /// `(|re z| - i|im z|)^2 + c`, likewise
+    BurningShip,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FractalSpec {
+    #[serde(rename = "type")]
+    pub kind: FractalType,
+    #[serde(default)]
+    pub formula: Formula,
+    /// The seed of a Julia set, as `[re, im]`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<[f64; 2]>,
+    /// What each step raises the orbit to.
+    #[serde(default = "default_power", skip_serializing_if = "is_default_power")]
+    pub power: f64,
+    /// How far from the origin an orbit has to get to escape.
+    #[serde(default = "default_bailout", skip_serializing_if = "is_default_bailout")]
+    pub bailout: f64,
+}
+
+fn default_power() -> f64 {
+    POWER
+}
+
+fn is_default_power(power: &f64) -> bool {
+    *power == POWER
+}
+
+fn default_bailout() -> f64 {
+    BAILOUT
+}
+
+fn is_default_bailout(bailout: &f64) -> bool {
+    *bailout == BAILOUT
+}
+
+impl FractalSpec {
+    pub fn new(fractal: Fractal, orbit: Orbit) -> FractalSpec {
+        let (kind, formula, seed) = match fractal {
+            Fractal::Mandelbrot => (FractalType::Mandelbrot, Formula::Quadratic, None),
+            Fractal::BurningShip => (FractalType::Mandelbrot, Formula::BurningShip, None),
+            Fractal::Julia(seed) => (FractalType::Julia, Formula::Quadratic, Some([seed.re, seed.im])),
+        };
+        FractalSpec { kind, formula, seed, power: orbit.power, bailout: orbit.bailout }
+    }
+
+    /// How the fractal's orbits are followed.
+    pub fn orbit(&self) -> Orbit {
+        Orbit { power: self.power, bailout: self.bailout }
+    }
+
+    /// The fractal described, if it is one we can render.
+    pub fn fractal(&self) -> Result<Fractal> {
+        match (self.kind, self.formula, self.seed) {
+            (FractalType::Mandelbrot, _, Some(_)) => Err(Error::BadScene("only a julia set has a seed".to_string())),
+            (FractalType::Mandelbrot, Formula::Quadratic, None) => Ok(Fractal::Mandelbrot),
+            (FractalType::Mandelbrot, Formula::BurningShip, None) => Ok(Fractal::BurningShip),
+            (FractalType::Julia, Formula::Quadratic, seed) => Ok(Fractal::Julia(seed.map(point).unwrap_or(DEFAULT_SEED))),
+            (FractalType::Julia, Formula::BurningShip, _) => Err(Error::BadScene("there are no burning ship julia sets yet".to_string())),
+        }
+    }
+}
+
+/// The size of the image and the region of the plane it shows, with points
+/// written `[re, im]`.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct View {
+    pub size: [usize; 2],
+    pub upper_left: [f64; 2],
+    pub lower_right: [f64; 2],
+    /// How far the view is turned counterclockwise about the center of the
+    /// region, in degrees.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rotation: f64,
+}
+
+impl View {
+    pub fn new(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> View {
+        View {
+            size: [bounds.0, bounds.1],
+            upper_left: [upper_left.re, upper_left.im],
+            lower_right: [lower_right.re, lower_right.im],
+            rotation: 0.0,
+        }
+    }
+
+    /// The view `request` renders.
+    pub fn of(request: &RenderRequest) -> View {
+        View { rotation: request.rotation, ..View::new(request.bounds, request.upper_left, request.lower_right) }
+    }
+}
+
+fn is_zero(x: &f64) -> bool {
+    *x == 0.0
+}
+
+fn point([re, im]: [f64; 2]) -> Complex<f64> {
+    Complex { re, im }
+}
+
+/// The settings that become a `Coloring`, with the palette given by name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColorSpec {
+    pub palette: String,
+    /// Entries in the palette's lookup table, or 0 for none.
+    pub resolution: usize,
+    pub mode: ColorMode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alpha: Option<AlphaMode>,
+    /// How far around the palette the colors are moved, in trips around it.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub offset: f64,
+}
+
+impl Default for ColorSpec {
+    fn default() -> Self {
+        ColorSpec { palette: "wikipedia".to_string(), resolution: color::DEFAULT_RESOLUTION, mode: ColorMode::Count, alpha: None, offset: 0.0 }
+    }
+}
+
+impl ColorSpec {
+    pub fn coloring(&self) -> Result<Coloring> {
+        Ok(Coloring {
+            palette: color::colors(&self.palette)?.with_cache(self.resolution),
+            mode: self.mode,
+            alpha: self.alpha,
+            offset: self.offset,
+        })
+    }
+}
+
+/// Where the image goes, and how it is encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputSpec {
+    pub file: String,
+    /// Where to save the escape field as well, if anywhere.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<ImageFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub depth: Option<Depth>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<u8>,
+}
+
+impl OutputSpec {
+    pub fn new(file: &str, field: Option<&str>, options: &OutputOptions) -> OutputSpec {
+        OutputSpec {
+            file: file.to_string(),
+            field: field.map(str::to_string),
+            format: options.format,
+            depth: options.depth,
+            quality: options.quality,
+        }
+    }
+
+    pub fn options(&self) -> OutputOptions {
+        OutputOptions { format: self.format, depth: self.depth, quality: self.quality, text: Vec::new() }
+    }
+}
+
+impl Scene {
+    /// The request that renders this scene, once it has been checked.
+    pub fn to_request(&self) -> Result<RenderRequest> {
+        if self.antialias.is_some_and(|settings| settings.samples == 0) {
+            return Err(Error::BadScene("antialiasing needs at least one sample".to_string()));
+        }
+        self.fractal.orbit().check().map_err(Error::BadScene)?;
+        if !self.view.rotation.is_finite() {
+            return Err(Error::BadScene(format!("can't turn the view by {} degrees", self.view.rotation)));
+        }
+        let request = RenderRequest {
+            fractal: self.fractal.fractal()?,
+            orbit: self.fractal.orbit(),
+            bounds: (self.view.size[0], self.view.size[1]),
+            upper_left: point(self.view.upper_left),
+            lower_right: point(self.view.lower_right),
+            rotation: self.view.rotation,
+            coloring: self.color.coloring()?,
+            scheduler: self.render,
+            antialias: self.antialias,
+        };
+        request.validate()?;
+        Ok(request)
+    }
+
+    pub fn from_toml(s: &str) -> Result<Scene> {
+        toml::from_str(s).map_err(|e| Error::BadScene(e.to_string()))
+    }
+
+    pub fn from_json(s: &str) -> Result<Scene> {
+        serde_json::from_str(s).map_err(|e| Error::BadScene(e.to_string()))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("scenes are always valid toml")
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("scenes are always valid json") + "\n"
+    }
+
+    /// Text chunks recording this scene and the `software` that rendered it,
+    /// for `OutputOptions::text`.
+    pub fn png_text(&self, software: &str) -> Vec<(String, String)> {
+        vec![("Software".to_string(), software.to_string()), (SCENE_KEYWORD.to_string(), self.to_toml())]
+    }
+
+    /// The scene stored in the PNG file at `path` when it was rendered.
+    pub fn from_image(path: &str) -> Result<Scene> {
+        let text = read_png_text(path).map_err(|e| Error::from(e).in_file(path))?;
+        match text.iter().find(|(keyword, _)| keyword == SCENE_KEYWORD) {
+            Some((_, scene)) => Scene::from_toml(scene),
+            None => Err(Error::BadScene("the image doesn't say how it was rendered".to_string())),
+        }.map_err(|e| e.in_file(path))
+    }
+
+    /// Read the scene file at `path`, which is JSON if its name ends in
+    /// `.json` and TOML otherwise.
+    pub fn read(path: &str) -> Result<Scene> {
+        let text = fs::read_to_string(path).map_err(|e| Error::from(e).in_file(path))?;
+        if is_json(path) { Scene::from_json(&text) } else { Scene::from_toml(&text) }.map_err(|e| e.in_file(path))
+    }
+
+    /// Write the scene to `path`, as JSON if its name ends in `.json` and
+    /// TOML otherwise.
+    pub fn write(&self, path: &str) -> Result<()> {
+        let text = if is_json(path) { self.to_json() } else { self.to_toml() };
+        fs::write(path, text).map_err(|e| Error::from(e).in_file(path))
+    }
+}
+
+pub(crate) fn is_json(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::antialias::{Filter, Pattern};
+    use crate::scheduler::TileOrder;
+
+    fn everything() -> Scene {
+        Scene {
+            fractal: FractalSpec::new(Fractal::Julia(Complex { re: -0.8, im: 0.156 }), Orbit { power: 3.0, bailout: 10.0 }),
+            view: View { rotation: 30.0, ..View::new((300, 200), Complex { re: -1.5, im: 1.0 }, Complex { re: 1.5, im: -1.0 }) },
+            render: Scheduler { tile_size: 32, order: TileOrder::Hilbert, accel: crate::accel::Accel::BoundaryTrace, limit: 1000 },
+            color: ColorSpec { palette: "magma".to_string(), resolution: 0, mode: ColorMode::Distance, alpha: Some(AlphaMode::Distance(2.5)), offset: 0.25 },
+            antialias: Some(Antialias { samples: 9, pattern: Pattern::BlueNoise, filter: Filter::Lanczos, threshold: 0.0 }),
+            output: OutputSpec {
+                file: "julia.tiff".to_string(),
+                field: Some("julia.mbef".to_string()),
+                format: Some(ImageFormat::Tiff),
+                depth: Some(Depth::Sixteen),
+                quality: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let scene = everything();
+        assert_eq!(Scene::from_toml(&scene.to_toml()).unwrap(), scene);
+        assert_eq!(Scene::from_json(&scene.to_json()).unwrap(), scene);
+        let plain = Scene { antialias: None, color: ColorSpec::default(), ..scene };
+        assert_eq!(Scene::from_toml(&plain.to_toml()).unwrap(), plain);
+
+        let request = everything().to_request().unwrap();
+        assert_eq!((request.rotation, request.orbit), (30.0, Orbit { power: 3.0, bailout: 10.0 }));
+        assert_eq!(View::of(&request), everything().view);
+    }
+
+    #[test]
+    fn test_defaults_match_the_command_line() {
+        let scene = Scene::from_toml(
+            "[fractal]\ntype = \"mandelbrot\"\nformula = \"burning-ship\"\n\n\
+             [view]\nsize = [80, 60]\nupper_left = [-2.5, 1.5]\nlower_right = [1.5, -1.5]\n\n\
+             [antialias]\nsamples = 4\n\n\
+             [output]\nfile = \"bs.png\"\ndepth = \"16\"\n").unwrap();
+        let request = scene.to_request().unwrap();
+        let expected = RenderRequest::burning_ship().size(80, 60);
+        assert_eq!(request.fractal, expected.fractal);
+        assert_eq!((request.upper_left, request.lower_right), (expected.upper_left, expected.lower_right));
+        assert_eq!(request.scheduler, expected.scheduler);
+        assert_eq!(request.coloring.mode, expected.coloring.mode);
+        assert_eq!(request.antialias, Some(Antialias { samples: 4, pattern: Pattern::RotatedGrid, filter: Filter::Tent,
+                                                       threshold: crate::antialias::DEFAULT_THRESHOLD }));
+        assert_eq!(scene.output.options().depth, Some(Depth::Sixteen));
+        assert_eq!(FractalSpec { seed: None, ..FractalSpec::new(Fractal::Julia(DEFAULT_SEED), Orbit::default()) }.fractal().unwrap(),
+                   Fractal::Julia(DEFAULT_SEED));
+    }
+
+    #[test]
+    fn test_bad_scenes() {
+        let mut scene = everything();
+        scene.fractal.formula = Formula::BurningShip;
+        assert!(matches!(scene.to_request(), Err(Error::BadScene(_))));
+        scene.fractal = FractalSpec { seed: Some([0.0, 1.0]), ..FractalSpec::new(Fractal::Mandelbrot, Orbit::default()) };
+        assert!(matches!(scene.to_request(), Err(Error::BadScene(_))));
+        scene.fractal.seed = None;
+        scene.color.palette = "plaid".to_string();
+        assert!(matches!(scene.to_request(), Err(Error::UnknownPalette(_))));
+        let mut scene = everything();
+        scene.antialias.as_mut().unwrap().samples = 0;
+        assert!(matches!(scene.to_request(), Err(Error::BadScene(_))));
+        let mut scene = everything();
+        scene.fractal.power = 0.5;
+        assert!(matches!(scene.to_request(), Err(Error::BadScene(_))));
+
+        let misplaced = everything().to_toml().replace("[render]\n", "[render]\npower = 3.0\n");
+        assert!(matches!(Scene::from_toml(&misplaced), Err(Error::BadScene(_))));
+        let typo = everything().to_toml().replace("iterations", "iteratoins");
+        assert!(matches!(Scene::from_toml(&typo), Err(Error::BadScene(_))));
+        let accel = everything().to_json().replace("\"boundary\"", "\"sideways\"");
+        assert!(matches!(Scene::from_json(&accel), Err(Error::BadScene(_))));
+    }
+}
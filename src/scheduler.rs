@@ -0,0 +1,359 @@
+use std::ops::Range;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+
+use num::Complex;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accel::{self, Accel};
+use crate::escape::{EscapeField, EscapeSample};
+use crate::fractal::{view_point, Fractal, Orbit, LIMIT};
+use crate::progress;
+
+/// The order in which tiles are handed to the render threads.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TileOrder {
+    /// Left to right, top to bottom.
+    Scanline,
+    /// Outwards from the center of the image, ring by ring.
+    Spiral,
+    /// Along a Hilbert curve, which keeps consecutive tiles next to each
+    /// other.
+    Hilbert,
+}
+
+pub const TILEORDERS: &str = "scanline (default)\nspiral\nhilbert\n";
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "scanline" => Ok(TileOrder::Scanline),
+            "spiral" => Ok(TileOrder::Spiral),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(format!("unknown tile order {}", s)),
+        }
+    }
+}
+
+/// A rectangle of pixels, `width` by `height` with its upper left corner at
+/// column `x` and row `y` of the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Tile {
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copy `samples`, which holds this tile's pixels row by row, into its
+    /// place in `field`.
+    pub fn copy_into(&self, samples: &[EscapeSample], field: &mut EscapeField) {
+        for (row, line) in samples.chunks(self.width).enumerate() {
+            let start = (self.y + row) * field.bounds.0 + self.x;
+            field.samples[start..start + self.width].copy_from_slice(line);
+        }
+    }
+}
+
+pub const DEFAULT_TILE_SIZE: usize = 64;
+
+/// Splits an image into square tiles and renders them on the rayon thread
+/// pool, in a chosen order.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scheduler {
+    pub tile_size: usize,
+    pub order: TileOrder,
+    pub accel: Accel,
+    /// How many iterations a point gets before it is taken to be inside.
+    #[serde(rename = "iterations")]
+    pub limit: usize,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler {
+            tile_size: DEFAULT_TILE_SIZE,
+            order: TileOrder::Scanline,
+            accel: Accel::None,
+            limit: LIMIT,
+        }
+    }
+}
+
+impl Scheduler {
+    /// The tiles covering an image of size `bounds`, in rendering order. Tiles
+    /// along the right and bottom edges are cut short to fit.
+    pub fn tiles(&self, bounds: (usize, usize)) -> Vec<Tile> {
+        let size = self.tile_size.max(1);
+        let (across, down) = (bounds.0.div_ceil(size), bounds.1.div_ceil(size));
+        let mut grid: Vec<(usize, usize)> = (0..down).flat_map(|ty| (0..across).map(move |tx| (tx, ty))).collect();
+        match self.order {
+            TileOrder::Scanline => (),
+            TileOrder::Spiral => {
+                let center = ((across as f64 - 1.0) / 2.0, (down as f64 - 1.0) / 2.0);
+                grid.sort_by(|&a, &b| spiral_key(a, center).partial_cmp(&spiral_key(b, center)).unwrap());
+            }
+            TileOrder::Hilbert => {
+                let side = across.max(down).next_power_of_two();
+                grid.sort_by_key(|&(tx, ty)| hilbert_index(side, tx, ty));
+            }
+        }
+        grid.into_iter().map(|(tx, ty)| Tile {
+            x: tx * size,
+            y: ty * size,
+            width: size.min(bounds.0 - tx * size),
+            height: size.min(bounds.1 - ty * size),
+        }).collect()
+    }
+
+    /// Render `fractal` into `field`, one tile at a time, following every
+    /// orbit as `orbit` says and turning the image `rotation` degrees
+    /// counterclockwise about the center of its region. `on_tile` is called
+    /// on the calling thread as each tile finishes, with the tile and its
+    /// samples, after they have been copied into `field`. If the render is
+    /// cancelled, the tiles already started are finished and the rest are
+    /// left alone.
+    pub fn render<F>(&self, field: &mut EscapeField, fractal: Fractal, orbit: Orbit, rotation: f64, on_tile: F)
+        where F: FnMut(&Tile, &[EscapeSample]) {
+        let tiles = self.tiles(field.bounds);
+        self.render_tiles(field, fractal, orbit, rotation, &tiles, on_tile);
+    }
+
+    /// Like `render`, but only for `tiles`, which are rendered in the order
+    /// given. The rest of `field` is left as it is.
+    ///
+    /// Called from inside the rayon pool, as when several frames are
+    /// rendered at once, waiting on the pool for tiles would tie up one of
+    /// the threads that has to render them. Then the tiles are rendered with
+    /// this thread helping, and `on_tile` only hears of them once they all
+    /// are.
+    pub fn render_tiles<F>(&self, field: &mut EscapeField, fractal: Fractal, orbit: Orbit, rotation: f64, tiles: &[Tile], mut on_tile: F)
+        where F: FnMut(&Tile, &[EscapeSample]) {
+        let (bounds, upper_left, lower_right) = (field.bounds, field.upper_left, field.lower_right);
+        if rayon::current_thread_index().is_some() {
+            let rendered: Vec<Option<Vec<EscapeSample>>> = tiles.par_iter().map(|tile| {
+                if progress::cancelled() {
+                    return None
+                }
+                let mut samples = vec![EscapeSample::INTERIOR; tile.len()];
+                self.render_tile(fractal, orbit, rotation, &mut samples, tile, bounds, upper_left, lower_right);
+                Some(samples)
+            }).collect();
+            for (tile, samples) in tiles.iter().zip(rendered) {
+                if let Some(samples) = samples {
+                    tile.copy_into(&samples, field);
+                    on_tile(tile, &samples);
+                }
+            }
+            return
+        }
+
+        let next = AtomicUsize::new(0);
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::scope(|scope| {
+            // rendering happens on the rayon pool, while this thread collects
+            // the finished tiles; each pool thread takes the next tile in
+            // line until there are none left, or the render is cancelled
+            scope.spawn(|| {
+                let sender = sender;
+                (0..rayon::current_num_threads()).into_par_iter().for_each(|_| {
+                    while let Some(tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if progress::cancelled() {
+                            return
+                        }
+                        let mut samples = vec![EscapeSample::INTERIOR; tile.len()];
+                        self.render_tile(fractal, orbit, rotation, &mut samples, tile, bounds, upper_left, lower_right);
+                        if sender.send((*tile, samples)).is_err() {
+                            return
+                        }
+                    }
+                });
+            });
+            for (tile, samples) in receiver {
+                tile.copy_into(&samples, field);
+                on_tile(&tile, &samples);
+            }
+        });
+    }
+
+    /// Render just `rows` of an image of size `bounds`, returning their
+    /// samples row by row. The band is cut into tiles across its width, which
+    /// are rendered in parallel.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_rows(&self,
+                       fractal: Fractal,
+                       orbit: Orbit,
+                       rotation: f64,
+                       bounds: (usize, usize),
+                       upper_left: Complex<f64>,
+                       lower_right: Complex<f64>,
+                       rows: Range<usize>) -> Vec<EscapeSample> {
+        let size = self.tile_size.max(1);
+        let height = rows.end - rows.start;
+        let tiles: Vec<Tile> = (0..bounds.0).step_by(size).map(|x| Tile {
+            x,
+            y: rows.start,
+            width: size.min(bounds.0 - x),
+            height,
+        }).collect();
+        let rendered: Vec<Vec<EscapeSample>> = tiles.par_iter().map(|tile| {
+            let mut samples = vec![EscapeSample::INTERIOR; tile.len()];
+            self.render_tile(fractal, orbit, rotation, &mut samples, tile, bounds, upper_left, lower_right);
+            samples
+        }).collect();
+
+        let mut band = vec![EscapeSample::INTERIOR; bounds.0 * height];
+        for (tile, samples) in tiles.iter().zip(rendered) {
+            for (row, line) in samples.chunks(tile.width).enumerate() {
+                let start = row * bounds.0 + tile.x;
+                band[start..start + tile.width].copy_from_slice(line);
+            }
+        }
+        band
+    }
+
+    /// Render `tile` of an image of size `bounds` into a buffer of escape
+    /// samples.
+    ///
+    /// `samples` holds one `EscapeSample` per pixel of the tile. The
+    /// `upper_left` and `lower_right` arguments specify points on the complex
+    /// plane corresponding to the upper-left and lower-right corners of the
+    /// whole image, before it is turned `rotation` degrees, so every pixel
+    /// is placed exactly where it would be in a single pass over the image.
+    /// The scheduler's acceleration is tried first, and every pixel is
+    /// iterated as `orbit` says if it can't be used.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_tile(&self,
+                       fractal: Fractal,
+                       orbit: Orbit,
+                       rotation: f64,
+                       samples: &mut [EscapeSample],
+                       tile: &Tile,
+                       bounds: (usize, usize),
+                       upper_left: Complex<f64>,
+                       lower_right: Complex<f64>) {
+        assert!(samples.len() == tile.len());
+        if accel::render_tile(self, fractal, orbit, rotation, samples, tile, bounds, upper_left, lower_right) {
+            return
+        }
+        for row in 0..tile.height {
+            for col in 0..tile.width {
+                let point = view_point(bounds, ((tile.x + col) as f64, (tile.y + row) as f64), upper_left, lower_right, rotation);
+                samples[row * tile.width + col] = fractal.escape(point, self.limit, orbit);
+            }
+        }
+    }
+}
+
+/// Sort key for `TileOrder::Spiral`: which ring around the center the tile is
+/// in, then how far around that ring it is.
+fn spiral_key(tile: (usize, usize), center: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (tile.0 as f64 - center.0, tile.1 as f64 - center.1);
+    (dx.abs().max(dy.abs()).round(), dy.atan2(dx))
+}
+
+/// The distance along a Hilbert curve filling a `side` by `side` grid, where
+/// `side` is a power of two, at which it passes through `(x, y)`.
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tiles_cover_image() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let scheduler = Scheduler { tile_size: 16, order, ..Default::default() };
+            let tiles = scheduler.tiles((50, 33));
+            assert_eq!(tiles.len(), 4 * 3);
+            assert_eq!(tiles.iter().map(Tile::len).sum::<usize>(), 50 * 33);
+        }
+    }
+
+    #[test]
+    fn test_hilbert_index() {
+        let order: Vec<usize> = [(0, 0), (0, 1), (1, 1), (1, 0)].iter().map(|&(x, y)| hilbert_index(2, x, y)).collect();
+        assert_eq!(order, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_spiral_starts_in_center() {
+        let scheduler = Scheduler { tile_size: 10, order: TileOrder::Spiral, ..Default::default() };
+        let first = scheduler.tiles((50, 50))[0];
+        assert_eq!((first.x, first.y), (20, 20));
+    }
+
+    #[test]
+    fn test_render_matches_single_pass() {
+        let bounds = (37, 21);
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let mut field = EscapeField::new(bounds, upper_left, lower_right);
+        let mut seen = 0;
+        Scheduler { tile_size: 8, order: TileOrder::Hilbert, ..Default::default() }.render(&mut field, Fractal::Mandelbrot, Orbit::default(), 0.0, |tile, _| seen += tile.len());
+        assert_eq!(seen, bounds.0 * bounds.1);
+
+        let whole = Tile { x: 0, y: 0, width: bounds.0, height: bounds.1 };
+        let mut expected = vec![EscapeSample::INTERIOR; whole.len()];
+        Scheduler { accel: Accel::None, ..Default::default() }.render_tile(Fractal::Mandelbrot, Orbit::default(), 0.0, &mut expected, &whole, bounds, upper_left, lower_right);
+        assert_eq!(field.samples, expected);
+
+        let band = Scheduler { tile_size: 8, ..Default::default() }.render_rows(Fractal::Mandelbrot, Orbit::default(), 0.0, bounds, upper_left, lower_right, 5..12);
+        assert_eq!(band, &expected[5 * bounds.0..12 * bounds.0]);
+    }
+
+    #[test]
+    fn test_render_inside_pool() {
+        // renders started from the pool's own threads, more of them than
+        // there are threads, so none is left over for the tiles unless the
+        // renders help with them; this needs a pool of several threads,
+        // which the global one has unless RAYON_NUM_THREADS says otherwise
+        let _ = rayon::ThreadPoolBuilder::new().num_threads(4).build_global();
+        let bounds = (64, 48);
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let scheduler = Scheduler { tile_size: 8, ..Default::default() };
+        let fields: Vec<EscapeField> = (0..2 * rayon::current_num_threads()).into_par_iter().map(|_| {
+            let mut field = EscapeField::new(bounds, upper_left, lower_right);
+            let mut seen = 0;
+            scheduler.render(&mut field, Fractal::Mandelbrot, Orbit::default(), 0.0, |tile, _| seen += tile.len());
+            assert_eq!(seen, bounds.0 * bounds.1);
+            field
+        }).collect();
+
+        let mut expected = EscapeField::new(bounds, upper_left, lower_right);
+        scheduler.render(&mut expected, Fractal::Mandelbrot, Orbit::default(), 0.0, |_, _| ());
+        assert!(fields.iter().all(|field| field.samples == expected.samples));
+    }
+}
@@ -0,0 +1,139 @@
+use std::sync::mpsc;
+
+use image::ImageResult;
+use num::Complex;
+
+use crate::antialias::{antialias_band, Antialias, Band};
+use crate::color::{colorize, AlphaMode, Coloring};
+use crate::escape::EscapeField;
+use crate::fractal::{subpixel_to_point, Fractal, Orbit};
+use crate::output::{parameter_error, write_image_strips, OutputOptions};
+use crate::progress::{self, Progress};
+use crate::scheduler::Scheduler;
+
+/// How many finished strips may wait for the encoder when none is asked for.
+pub const DEFAULT_IN_FLIGHT: usize = 4;
+
+/// An image rendered straight to a file, one strip of tiles at a time, so
+/// that only a few strips are ever in memory however big the image is.
+pub struct StreamRender<'a> {
+    pub fractal: Fractal,
+    pub orbit: Orbit,
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+    /// How far the image is turned counterclockwise, in degrees.
+    pub rotation: f64,
+    pub scheduler: &'a Scheduler,
+    pub coloring: &'a Coloring,
+    pub antialias: Option<&'a Antialias>,
+    /// How many finished strips may wait for the encoder before rendering
+    /// stops to let it catch up.
+    pub in_flight: usize,
+    /// Counts finished strips.
+    pub progress: &'a Progress,
+    /// Whether a cancelled render should still finish the file, with the
+    /// strips it didn't get to left blank. Otherwise the file stops short.
+    pub save_partial: bool,
+}
+
+impl<'a> StreamRender<'a> {
+    /// Render the image and write it to `filename`, which must be a PNG or
+    /// TIFF file. Strips are as tall as the scheduler's tiles, and are
+    /// rendered on one thread while the encoder runs on this one.
+    pub fn write(&self, filename: &str, options: &OutputOptions) -> ImageResult<()> {
+        if self.coloring.alpha == Some(AlphaMode::Escape) {
+            return Err(parameter_error("escape alpha is scaled to the whole image, so it can't be streamed".to_string()));
+        }
+        let rows = self.scheduler.tile_size.max(1);
+        let (sender, receiver) = mpsc::sync_channel(self.in_flight.max(1));
+
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                for top in (0..self.bounds.1).step_by(rows) {
+                    let height = rows.min(self.bounds.1 - top);
+                    let strip = if !progress::cancelled() {
+                        self.strip(top, height)
+                    } else if self.save_partial {
+                        vec![0.0; self.bounds.0 * height * self.coloring.channels()]
+                    } else {
+                        return
+                    };
+                    // the encoder has given up if nobody is listening
+                    if sender.send(strip).is_err() {
+                        return
+                    }
+                }
+            });
+            write_image_strips(receiver, self.bounds, self.coloring.channels(), rows, filename, options)
+        })
+    }
+
+    /// The colored pixels of `height` rows starting at row `top`.
+    ///
+    /// Supersampling looks for edges between neighbouring pixels, so when it
+    /// is on, the rows either side of the strip are rendered and colored too,
+    /// then dropped.
+    fn strip(&self, top: usize, height: usize) -> Vec<f32> {
+        let context = if self.antialias.is_some() { 1 } else { 0 };
+        let (first, last) = (top.saturating_sub(context), (top + height + context).min(self.bounds.1));
+        let corner = |row: usize| subpixel_to_point(self.bounds, (0.0, row as f64), self.upper_left, self.lower_right);
+        let mut field = EscapeField::new((self.bounds.0, last - first),
+                                         corner(first),
+                                         Complex { re: self.lower_right.re, im: corner(last).im });
+        field.samples = self.scheduler.render_rows(self.fractal, self.orbit, self.rotation, self.bounds, self.upper_left, self.lower_right, first..last);
+        self.progress.record(&field.samples[(top - first) * self.bounds.0..(top - first + height) * self.bounds.0]);
+
+        let mut pixels = colorize(&field, self.coloring);
+        if let Some(settings) = self.antialias {
+            let band = Band { first_row: first, image_bounds: self.bounds, upper_left: self.upper_left, lower_right: self.lower_right, rotation: self.rotation };
+            antialias_band(&mut pixels, &field, &band, self.coloring, self.fractal, self.scheduler, self.orbit, settings);
+        }
+        let row_len = self.bounds.0 * self.coloring.channels();
+        pixels.drain((top - first + height) * row_len..);
+        pixels.drain(..(top - first) * row_len);
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::color;
+    use crate::output::write_image;
+
+    #[test]
+    fn test_stream_matches_whole_image() {
+        let bounds = (45, 37);
+        let (upper_left, lower_right) = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let scheduler = Scheduler { tile_size: 8, ..Default::default() };
+        let coloring = Coloring { palette: color::colors("magma").unwrap(), mode: color::ColorMode::Smooth, alpha: Some(AlphaMode::Interior), offset: 0.0 };
+
+        let mut field = EscapeField::new(bounds, upper_left, lower_right);
+        scheduler.render(&mut field, Fractal::Mandelbrot, Orbit::default(), 0.0, |_, _| ());
+        let whole = std::env::temp_dir().join("mandelbrot-test-whole.png");
+        write_image(&colorize(&field, &coloring), bounds, whole.to_str().unwrap(), &OutputOptions::default()).unwrap();
+
+        let render = StreamRender {
+            fractal: Fractal::Mandelbrot,
+            orbit: Orbit::default(),
+            bounds,
+            upper_left,
+            lower_right,
+            rotation: 0.0,
+            scheduler: &scheduler,
+            coloring: &coloring,
+            antialias: None,
+            in_flight: 1,
+            progress: &Progress::new(progress::ProgressStyle::Quiet, "strips", 5, bounds.0 * bounds.1, scheduler.limit),
+            save_partial: false,
+        };
+        for extension in ["png", "tiff"] {
+            let streamed = std::env::temp_dir().join(format!("mandelbrot-test-streamed.{}", extension));
+            render.write(streamed.to_str().unwrap(), &OutputOptions::default()).unwrap();
+            assert_eq!(image::open(&streamed).unwrap().to_rgba8(), image::open(&whole).unwrap().to_rgba8());
+            std::fs::remove_file(streamed).unwrap();
+        }
+        std::fs::remove_file(whole).unwrap();
+    }
+}